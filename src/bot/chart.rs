@@ -0,0 +1,240 @@
+use ab_glyph::FontRef;
+use chrono::{DateTime, Duration, FixedOffset, Utc};
+use image::{ImageFormat, Rgb, RgbImage};
+use std::io::Cursor;
+
+use crate::bot::error::Result;
+use crate::bot::text::{self, export_error};
+
+const WIDTH: u32 = 1200;
+const LINE_CHART_HEIGHT: u32 = 700;
+const PADDING: u32 = 24;
+const TITLE_FONT_SIZE: f32 = 30.0;
+const TITLE_HEIGHT: u32 = 64;
+const FONT_SIZE: f32 = 20.0;
+const BAR_HEIGHT: u32 = 32;
+const BAR_GAP: u32 = 12;
+/// Longer code labels are cut off with an ellipsis
+const MAX_LABEL_WIDTH: f32 = 360.0;
+/// Room for the axis labels of a line chart
+const AXIS_WIDTH: u32 = 64;
+const AXIS_HEIGHT: u32 = 40;
+const LEGEND_WIDTH: u32 = 260;
+/// Approximate number of ticks on each axis
+const TICKS: i64 = 6;
+/// Lines a line chart has colors for; further series are left out
+pub const MAX_SERIES: usize = PALETTE.len();
+
+const BACKGROUND: Rgb<u8> = Rgb([255, 255, 255]);
+const TEXT: Rgb<u8> = Rgb([33, 33, 33]);
+const GRID: Rgb<u8> = Rgb([224, 224, 224]);
+/// Colors of bars and lines
+const PALETTE: [Rgb<u8>; 8] = [
+    Rgb([31, 119, 180]),
+    Rgb([255, 127, 14]),
+    Rgb([44, 160, 44]),
+    Rgb([214, 39, 40]),
+    Rgb([148, 103, 189]),
+    Rgb([140, 86, 75]),
+    Rgb([227, 119, 194]),
+    Rgb([127, 127, 127]),
+];
+
+/// A label and its check-ins per minute, as `Storage::get_timeline` counts them
+pub type Series = (String, Vec<(DateTime<Utc>, i64)>);
+
+struct Canvas {
+    image: RgbImage,
+    regular: FontRef<'static>,
+    bold: FontRef<'static>,
+}
+
+impl Canvas {
+    fn new(width: u32, height: u32) -> Result<Self> {
+        Ok(Self {
+            image: RgbImage::from_pixel(width, height, BACKGROUND),
+            regular: text::regular()?,
+            bold: text::bold()?,
+        })
+    }
+
+    fn title(&mut self, title: &str) {
+        let bold = self.bold.clone();
+        self.draw_text(&bold, TITLE_FONT_SIZE, title, PADDING as f32, PADDING as f32 / 2.0);
+    }
+
+    fn text(&mut self, text: &str, x: f32, y: f32) {
+        let regular = self.regular.clone();
+        self.draw_text(&regular, FONT_SIZE, text, x, y);
+    }
+
+    /// Blends text over the background
+    fn draw_text(&mut self, font: &FontRef, size: f32, text: &str, x: f32, y: f32) {
+        text::draw(font, size, text, x, y, |px, py, coverage| {
+            if let Some(pixel) = self.image.get_pixel_mut_checked(px, py) {
+                for channel in 0..3 {
+                    pixel.0[channel] = (pixel.0[channel] as f32 * (1.0 - coverage) + TEXT.0[channel] as f32 * coverage) as u8;
+                }
+            }
+        });
+    }
+
+    fn text_width(&self, text: &str) -> f32 {
+        text::width(&self.regular, FONT_SIZE, text)
+    }
+
+    fn rect(&mut self, x: u32, y: u32, width: u32, height: u32, color: Rgb<u8>) {
+        for px in x..(x + width).min(self.image.width()) {
+            for py in y..(y + height).min(self.image.height()) {
+                self.image.put_pixel(px, py, color);
+            }
+        }
+    }
+
+    /// A line `thickness` pixels wide
+    fn line(&mut self, from: (f32, f32), to: (f32, f32), thickness: u32, color: Rgb<u8>) {
+        let steps = (to.0 - from.0).abs().max((to.1 - from.1).abs()).ceil().max(1.0) as u32;
+        for step in 0..=steps {
+            let t = step as f32 / steps as f32;
+            let x = (from.0 + (to.0 - from.0) * t).round() as i64 - thickness as i64 / 2;
+            let y = (from.1 + (to.1 - from.1) * t).round() as i64 - thickness as i64 / 2;
+            if x >= 0 && y >= 0 {
+                self.rect(x as u32, y as u32, thickness, thickness, color);
+            }
+        }
+    }
+
+    fn into_png(self) -> Result<Vec<u8>> {
+        let mut png = Cursor::new(Vec::new());
+        self.image.write_to(&mut png, ImageFormat::Png).map_err(export_error)?;
+        Ok(png.into_inner())
+    }
+}
+
+/// Horizontal bars with their labels on the left and values at their ends
+pub fn create_bar_chart_png(title: &str, bars: &[(String, i64)]) -> Result<Vec<u8>> {
+    let height = TITLE_HEIGHT + bars.len().max(1) as u32 * (BAR_HEIGHT + BAR_GAP) + PADDING;
+    let mut canvas = Canvas::new(WIDTH, height)?;
+    canvas.title(title);
+
+    let labels = bars.iter()
+        .map(|(label, _)| truncate(&canvas, label, MAX_LABEL_WIDTH))
+        .collect::<Vec<String>>();
+    let label_width = labels.iter().map(|label| canvas.text_width(label)).fold(0.0, f32::max).ceil() as u32;
+    let max = bars.iter().map(|(_, value)| *value).max().unwrap_or(0).max(1);
+    let left = PADDING + label_width + PADDING / 2;
+    // Room for the value after the longest bar
+    let max_width = WIDTH - left - PADDING - canvas.text_width(&max.to_string()).ceil() as u32 - PADDING / 2;
+
+    for (i, ((_, value), label)) in bars.iter().zip(&labels).enumerate() {
+        let y = TITLE_HEIGHT + i as u32 * (BAR_HEIGHT + BAR_GAP);
+        let text_y = y as f32 + (BAR_HEIGHT as f32 - FONT_SIZE) / 2.0;
+        canvas.text(label, PADDING as f32, text_y);
+        let width = (*value as f64 / max as f64 * max_width as f64).round() as u32;
+        canvas.rect(left, y, width, BAR_HEIGHT, PALETTE[0]);
+        canvas.text(&value.to_string(), (left + width + PADDING / 2) as f32, text_y);
+    }
+
+    canvas.into_png()
+}
+
+/// Cumulative check-ins over time, a line per series, with times on the axis in the given time zone
+pub fn create_line_chart_png(title: &str, series: &[Series], offset: FixedOffset) -> Result<Vec<u8>> {
+    let mut canvas = Canvas::new(WIDTH, LINE_CHART_HEIGHT)?;
+    canvas.title(title);
+
+    let times = series.iter().flat_map(|(_, points)| points.iter().map(|(time, _)| *time));
+    let start = times.clone().min().unwrap_or_else(Utc::now);
+    // At least a minute, so that a single point still has a place
+    let end = times.max().unwrap_or(start).max(start + Duration::minutes(1));
+    let totals = series.iter().map(|(_, points)| points.iter().map(|(_, count)| count).sum::<i64>());
+    let step = nice_step(totals.clone().max().unwrap_or(0));
+    // Up to the next tick, so that the top of the axis is labeled
+    let max = ((totals.max().unwrap_or(0) + step - 1) / step).max(1) * step;
+
+    let left = PADDING + AXIS_WIDTH;
+    let top = TITLE_HEIGHT;
+    let right = WIDTH - LEGEND_WIDTH - PADDING;
+    let bottom = LINE_CHART_HEIGHT - AXIS_HEIGHT - PADDING;
+    let x_of = |time: DateTime<Utc>| {
+        left as f32 + (time - start).num_seconds() as f32 / (end - start).num_seconds() as f32 * (right - left) as f32
+    };
+    let y_of = |count: i64| bottom as f32 - count as f32 / max as f32 * (bottom - top) as f32;
+
+    let mut tick = 0;
+    while tick <= max {
+        let y = y_of(tick);
+        canvas.line((left as f32, y), (right as f32, y), 1, GRID);
+        let label = tick.to_string();
+        canvas.text(&label, left as f32 - canvas.text_width(&label) - PADDING as f32 / 2.0, y - FONT_SIZE / 2.0);
+        tick += step;
+    }
+    // Ticks at round local times
+    let seconds = time_step((end - start).num_minutes()) * 60;
+    let local_start = start.timestamp() + offset.local_minus_utc() as i64;
+    let mut tick = start + Duration::seconds((seconds - local_start.rem_euclid(seconds)) % seconds);
+    while tick <= end {
+        let x = x_of(tick);
+        canvas.line((x, top as f32), (x, bottom as f32), 1, GRID);
+        let label = tick.with_timezone(&offset).format("%H:%M").to_string();
+        canvas.text(&label, x - canvas.text_width(&label) / 2.0, (bottom + PADDING / 2) as f32);
+        tick += Duration::seconds(seconds);
+    }
+    canvas.line((left as f32, bottom as f32), (right as f32, bottom as f32), 2, TEXT);
+    canvas.line((left as f32, top as f32), (left as f32, bottom as f32), 2, TEXT);
+
+    for (i, (label, points)) in series.iter().take(MAX_SERIES).enumerate() {
+        let color = PALETTE[i];
+        // Steps: the total holds until the next check-in
+        let mut total = 0;
+        let mut previous = (x_of(start), y_of(0));
+        for (time, count) in points {
+            let x = x_of(*time);
+            canvas.line(previous, (x, previous.1), 3, color);
+            total += count;
+            canvas.line((x, previous.1), (x, y_of(total)), 3, color);
+            previous = (x, y_of(total));
+        }
+        canvas.line(previous, (right as f32, previous.1), 3, color);
+
+        let y = top + i as u32 * (FONT_SIZE as u32 + BAR_GAP);
+        canvas.rect(right + PADDING, y + 4, FONT_SIZE as u32 - 8, FONT_SIZE as u32 - 8, color);
+        // The total stays visible however long the label is
+        let total = format!(" ({})", total);
+        let max_width = (LEGEND_WIDTH - FONT_SIZE as u32 - PADDING / 2) as f32 - canvas.text_width(&total);
+        let label = truncate(&canvas, label, max_width) + &total;
+        canvas.text(&label, (right + PADDING + FONT_SIZE as u32) as f32, y as f32);
+    }
+
+    canvas.into_png()
+}
+
+/// 1, 2 or 5 times a power of ten, for about `TICKS` ticks up to `max`
+fn nice_step(max: i64) -> i64 {
+    let rough = (max / TICKS).max(1);
+    let mut power = 1;
+    while power * 10 <= rough {
+        power *= 10;
+    }
+    [1, 2, 5, 10].into_iter().map(|m| m * power).find(|step| *step >= rough).unwrap_or(10 * power)
+}
+
+/// Minutes between time ticks, for about `TICKS` ticks over `minutes`
+fn time_step(minutes: i64) -> i64 {
+    [1, 2, 5, 10, 15, 30, 60, 120, 180, 360, 720, 1440]
+        .into_iter()
+        .find(|step| minutes / step <= TICKS)
+        .unwrap_or(1440 * (minutes / 1440 / TICKS + 1))
+}
+
+/// Cuts text to `max_width` pixels, ending it with an ellipsis if anything was cut
+fn truncate(canvas: &Canvas, text: &str, max_width: f32) -> String {
+    if canvas.text_width(text) <= max_width {
+        return text.to_string();
+    }
+    let mut cut: String = text.to_string();
+    while !cut.is_empty() && canvas.text_width(&format!("{}…", cut)) > max_width {
+        cut.pop();
+    }
+    format!("{}…", cut.trim_end())
+}
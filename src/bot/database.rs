@@ -1,376 +1,788 @@
 //use std::sync::Arc;
 
-use sqlx::postgres::{PgPoolOptions,
-    //PgRow
-};
-//use sqlx::{FromRow, Row};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use futures::stream::BoxStream;
 use futures::TryStreamExt;
-use sqlx::Row;  // import for get() function on sqlx queries
+use std::collections::HashMap;
+use std::ops::Deref;
+
+use super::error::{Error, Result};
 
 pub struct Response {
     pub id: Option<i32>,
     pub speech_code: String,
-    pub telegram_id: i32,
+    pub telegram_id: i64,
+    pub rating: Option<i32>,
+    pub speech_title: Option<String>,
+    /// Check-in time; unknown for responses recorded before it was tracked
+    pub created_at: Option<DateTime<Utc>>,
 }
 
 #[derive(serde::Serialize)]
 pub struct FullResponse {
     pub id: Option<i32>,
     pub speech_code: String,
-    pub telegram_id: i32,
+    pub speech_title: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub telegram_id: i64,
     pub username: String,
     pub first_name: String,
     pub last_name: String,
+    pub rating: Option<i32>,
+}
+
+/// A speech code along with the talk it stands for
+#[derive(Clone)]
+pub struct Speech {
+    pub code: String,
+    pub title: Option<String>,
+    pub speaker: Option<String>,
+    pub room: Option<String>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    /// Check-in window in minutes before the start and after the end; `None` if check-ins are not time-limited
+    pub window: Option<(i32, i32)>,
+    /// Manual override of the window: `Some(true)` keeps check-ins open, `Some(false)` closed
+    pub checkin_override: Option<bool>,
+    /// Secret and period in seconds of the rotating check-in suffix; `None` for a static code
+    pub rotation: Option<(String, i32)>,
+}
+
+/// Whether a speech accepts check-ins at a given moment
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CheckInStatus {
+    Open,
+    /// The window opens at the given time
+    NotYetOpen(DateTime<Utc>),
+    /// The window closed at the given time
+    Over(DateTime<Utc>),
+    ClosedManually,
+}
+
+impl Speech {
+    pub fn checkin_status(&self, now: DateTime<Utc>) -> CheckInStatus {
+        match self.checkin_override {
+            Some(true) => return CheckInStatus::Open,
+            Some(false) => return CheckInStatus::ClosedManually,
+            None => (),
+        }
+        let Some((before, after)) = self.window else {
+            return CheckInStatus::Open;
+        };
+        if let Some(starts_at) = self.starts_at {
+            let opens_at = starts_at - Duration::minutes(before as i64);
+            if now < opens_at {
+                return CheckInStatus::NotYetOpen(opens_at);
+            }
+        }
+        if let Some(ends_at) = self.ends_at {
+            let closes_at = ends_at + Duration::minutes(after as i64);
+            if now > closes_at {
+                return CheckInStatus::Over(closes_at);
+            }
+        }
+        CheckInStatus::Open
+    }
+
+    /// Talk title with its code, or the bare code if the title is not set
+    pub fn display_name(&self) -> String {
+        display_name(&self.code, self.title.as_deref())
+    }
+}
+
+pub fn display_name(code: &str, title: Option<&str>) -> String {
+    match title {
+        Some(title) if !title.is_empty() => format!("«{}» ({})", title, code),
+        _ => code.to_string(),
+    }
+}
+
+/// Editable metadata field of a speech
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpeechField {
+    Title,
+    Speaker,
+    Room,
+}
+
+impl std::str::FromStr for SpeechField {
+    type Err = ();
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "title" => Ok(SpeechField::Title),
+            "speaker" => Ok(SpeechField::Speaker),
+            "room" => Ok(SpeechField::Room),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Check-ins covered by `Storage::full_responses`
+pub enum ResponseScope {
+    /// Every check-in, including ones with unknown codes
+    All,
+    /// Codes of an event, or all codes if `None`; check-ins with unknown codes are left out
+    Event(Option<i32>),
+    Code(String),
+    User(i64),
+}
+
+/// Narrows down the check-ins of an export on top of its scope; the default filter keeps them all
+#[derive(Clone, Debug, Default)]
+pub struct ExportFilter {
+    /// Codes to keep, any if empty
+    pub codes: Vec<String>,
+    /// Check-ins at or after this time; a bound leaves out check-ins of unknown time
+    pub from: Option<DateTime<Utc>>,
+    /// Check-ins before this time
+    pub until: Option<DateTime<Utc>>,
+    /// Users to keep, anyone if empty
+    pub users: Vec<i64>,
+}
+
+impl ExportFilter {
+    /// Whether a check-in passes, for backends without SQL
+    fn matches(&self, code: &str, telegram_id: i64, created_at: DateTime<Utc>) -> bool {
+        (self.codes.is_empty() || self.codes.iter().any(|c| c == code))
+            && (self.users.is_empty() || self.users.contains(&telegram_id))
+            && self.from.is_none_or(|from| created_at >= from)
+            && self.until.is_none_or(|until| created_at < until)
+    }
+}
+
+impl ResponseScope {
+    /// Bind parameters of the backends' query: code, user, known codes only, event
+    fn into_params(self) -> (Option<String>, Option<i64>, bool, Option<i32>) {
+        match self {
+            ResponseScope::All => (None, None, false, None),
+            ResponseScope::Event(event) => (None, None, true, event),
+            ResponseScope::Code(code) => (Some(code), None, false, None),
+            ResponseScope::User(telegram_id) => (None, Some(telegram_id), false, None),
+        }
+    }
 }
 
 pub struct User {
-    pub telegram_id: i32,
+    pub telegram_id: i64,
     pub username: String,
     pub first_name: String,
     pub last_name: String,
 }
 
-#[derive(serde::Serialize)]
-pub struct CodeResult {
-    pub speech_code: String,
-    pub responses: Vec<FullResponse>
+impl User {
+    /// Username, or the display name and id for users without one
+    pub fn label(&self) -> String {
+        if !self.username.is_empty() {
+            return self.username.clone();
+        }
+        let name = format!("{} {}", self.first_name, self.last_name);
+        match name.trim() {
+            "" => self.telegram_id.to_string(),
+            name => format!("{} ({})", name, self.telegram_id),
+        }
+    }
 }
 
+/// Check-ins of a single user; empty for users included without any
 pub struct UsernameResult {
-    pub telegram_id: i32,
-    pub username: String,
+    pub user: User,
     pub responses: Vec<FullResponse>
 }
 
-pub struct Database {
-    pub pool: sqlx::PgPool,
+/// Admin role, ordered by privilege: an owner can do everything
+/// an organizer can, and an organizer everything a viewer can
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    Viewer,
+    Organizer,
+    Owner,
 }
 
-impl Database {
-    pub async fn new(database_url: &str) -> Result<Self, sqlx::Error> {
-        let pool = PgPoolOptions::new()
-            .max_connections(5)
-            .connect(database_url)
-            .await?;
-
-        Ok(Self { pool })
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Organizer => "organizer",
+            Role::Owner => "owner",
+        }
     }
+}
 
-    pub async fn init(&self) -> Result<(), sqlx::Error> {
-        sqlx::query("CREATE TABLE IF NOT EXISTS responses (
-            id SERIAL PRIMARY KEY,
-            speech_code VARCHAR(32) NOT NULL,
-            telegram_id INT NOT NULL,
-            UNIQUE (telegram_id, speech_code)
-        )")
-            .execute(&self.pool)
-            .await?;
-        sqlx::query("CREATE TABLE IF NOT EXISTS allowed_codes (
-            id SERIAL PRIMARY KEY,
-            code VARCHAR(32) NOT NULL,
-            UNIQUE (code)
-        )")
-            .execute(&self.pool)
-            .await?;
-        sqlx::query("CREATE TABLE IF NOT EXISTS users (
-            telegram_id INT PRIMARY KEY,
-            first_name VARCHAR(64) NOT NULL,
-            last_name VARCHAR(64) NOT NULL,
-            username VARCHAR(32) NOT NULL,
-            UNIQUE (telegram_id)
-        )")
-            .execute(&self.pool)
-            .await?;
-        Ok(())
-    }
+impl std::str::FromStr for Role {
+    type Err = ();
 
-    pub async fn add_user(&self, user: &User) -> Result<(), sqlx::Error> {
-        // Check if user already exists
-        if sqlx::query("SELECT telegram_id FROM users WHERE telegram_id = $1")
-            .bind(&user.telegram_id)
-            .fetch_optional(&self.pool)
-            .await?
-            .is_some() {
-            return Ok(());
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "viewer" => Ok(Role::Viewer),
+            "organizer" => Ok(Role::Organizer),
+            "owner" => Ok(Role::Owner),
+            _ => Err(()),
         }
-        sqlx::query("INSERT INTO users (telegram_id, username, first_name, last_name) VALUES ($1, $2, $3, $4)")
-            .bind(&user.telegram_id)
-            .bind(&user.username)
-            .bind(&user.first_name)
-            .bind(&user.last_name)
-            .execute(&self.pool)
-            .await?;
-        Ok(())
     }
+}
+
+/// Free-text feedback left by an attendee for a speech
+#[derive(serde::Serialize)]
+pub struct Comment {
+    pub speech_code: String,
+    pub speech_title: Option<String>,
+    pub checked_in_at: Option<DateTime<Utc>>,
+    pub telegram_id: i64,
+    pub username: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub text: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuestionKind {
+    /// One of the predefined options
+    Choice,
+    /// An integer between the two bounds stored as options
+    Scale,
+    Text,
+}
 
-    pub async fn get_users(&self) -> Result<Vec<i32>, sqlx::Error> {
-        let mut users: Vec<i32> = Vec::new();
-        let mut rows = sqlx::query("SELECT telegram_id FROM users")
-            .fetch(&self.pool);
-        while let Some(row) = rows.try_next().await? {
-            users.push(row.get("telegram_id"));
+impl QuestionKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QuestionKind::Choice => "choice",
+            QuestionKind::Scale => "scale",
+            QuestionKind::Text => "text",
         }
-        Ok(users)
     }
+}
 
-    pub async fn add_code(&self, code: &str) -> Result<(), sqlx::Error> {
-        sqlx::query("INSERT INTO allowed_codes (code) VALUES ($1)")
-            .bind(code)
-            .execute(&self.pool)
-            .await.unwrap_or_default();  // See how to replace Result return statement to fit this unwrap
-        Ok(())
-    }
+impl std::str::FromStr for QuestionKind {
+    type Err = ();
 
-    pub async fn del_code(&self, code: &str) -> Result<(), sqlx::Error> {
-        sqlx::query("DELETE FROM allowed_codes WHERE code = $1")
-            .bind(code)
-            .execute(&self.pool)
-            .await.unwrap_or_default();
-        Ok(())
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "choice" => Ok(QuestionKind::Choice),
+            "scale" => Ok(QuestionKind::Scale),
+            "text" => Ok(QuestionKind::Text),
+            _ => Err(()),
+        }
     }
+}
 
-    pub async fn flush_responses(&self) -> Result<(), sqlx::Error> {
-        sqlx::query("DELETE FROM responses")
-            .execute(&self.pool)
-            .await.unwrap_or_default();
-        Ok(())
-    }
+/// A survey question attached to a speech code
+#[derive(Clone)]
+pub struct Question {
+    pub id: i32,
+    pub position: i32,
+    pub kind: QuestionKind,
+    pub text: String,
+    pub options: Vec<String>,
+}
+
+/// Survey answers of a single attendee, keyed by question id
+pub struct SurveyAnswers {
+    pub telegram_id: i64,
+    pub checked_in_at: Option<DateTime<Utc>>,
+    pub username: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub answers: HashMap<i32, String>,
+}
+
+/// Star ratings of a single speech code
+pub struct RatingSummary {
+    pub speech_code: String,
+    pub speech_title: Option<String>,
+    /// Number of ratings for each star, from 1 to 5
+    pub distribution: [i64; 5],
+}
 
-    pub async fn flush_codes(&self) -> Result<(), sqlx::Error> {
-        sqlx::query("DELETE FROM allowed_codes")
-            .execute(&self.pool)
-            .await.unwrap_or_default();
-        self.flush_responses().await?;
-        Ok(())
+impl RatingSummary {
+    pub fn count(&self) -> i64 {
+        self.distribution.iter().sum()
     }
 
-    pub async fn get_codes(&self) -> Result<Vec<String>, sqlx::Error> {
-        let mut codes: Vec<String> = Vec::new();
-        let mut rows = sqlx::query("SELECT * FROM allowed_codes")
-            .fetch(&self.pool);
-        while let Some(row) = rows.try_next().await? {
-            codes.push(row.get("code"));
+    pub fn average(&self) -> Option<f64> {
+        let count = self.count();
+        if count == 0 {
+            return None;
         }
-        Ok(codes)
+        let total: i64 = self.distribution.iter()
+            .enumerate()
+            .map(|(i, n)| (i as i64 + 1) * n)
+            .sum();
+        Some(total as f64 / count as f64)
+    }
+}
+
+/// Check-in counts of an event for `/stats`
+pub struct Stats {
+    /// Check-ins of each code, in the order of codes
+    pub codes: Vec<CodeCheckIns>,
+    /// Number of attendees who attended each number of talks, by the number of talks
+    pub talks_per_attendee: Vec<(i64, i64)>,
+    /// Check-ins since the time given to `Storage::get_stats`
+    pub recent: i64,
+}
+
+pub struct CodeCheckIns {
+    pub speech_code: String,
+    pub speech_title: Option<String>,
+    pub check_ins: i64,
+}
+
+impl Stats {
+    pub fn check_ins(&self) -> i64 {
+        self.codes.iter().map(|code| code.check_ins).sum()
     }
 
-    pub async fn is_code_allowed(&self, code: &str) -> Result<bool, sqlx::Error> {
-        let row = sqlx::query("SELECT * FROM allowed_codes WHERE code = $1")
-            .bind(code)
-            .fetch_one(&self.pool)
-            .await;
-        Ok(row.is_ok())
+    pub fn unique_attendees(&self) -> i64 {
+        self.talks_per_attendee.iter().map(|(_, attendees)| attendees).sum()
     }
 
-    pub async fn insert(&self, response: Response) -> Result<(), sqlx::Error> {
-        // Executes query; fails silently if unique match already exists
-        sqlx::query("INSERT INTO responses (speech_code, telegram_id) VALUES ($1, $2)")
-            .bind(response.speech_code)
-            .bind(response.telegram_id)
-            .execute(&self.pool)
-            .await.unwrap_or_default();
-        Ok(())
+    /// The code with the most check-ins, the earliest one on a tie; `None` if nobody checked in
+    pub fn busiest(&self) -> Option<&CodeCheckIns> {
+        self.codes.iter()
+            .filter(|code| code.check_ins > 0)
+            .rev()
+            .max_by_key(|code| code.check_ins)
     }
+}
+
+/// A prize draw with everything it was drawn from, so that it can be reproduced from its seed
+#[derive(Clone)]
+pub struct Raffle {
+    pub seed: String,
+    pub min_talks: i32,
+    /// Codes that counted as talks, all if empty
+    pub codes: Vec<String>,
+    /// Eligible users in the order the draw picks from
+    pub candidates: Vec<i64>,
+    /// In the order they were drawn
+    pub winners: Vec<i64>,
+    pub drawn_by: i64,
+}
 
-    // pub async fn get_all_responses(&self) -> Result<Vec<Response>, sqlx::Error> {
-    //     let mut responses: Vec<Response> = Vec::new();
-    //     let mut rows = sqlx::query("SELECT * FROM responses")
-    //         .fetch(&self.pool);
-
-    //     while let Some(row) = rows.try_next().await? {
-    //         responses.push(Response {
-    //             id: row.get("id"),
-    //             speech_code: row.get("speech_code"),
-    //             telegram_id: row.get("telegram_id"),
-    //         });
-    //     }
-
-    //     Ok(responses)
-    // }
-
-    pub async fn vec_response_to_fullresponse(&self, responses: Vec<Response>) -> Result<Vec<FullResponse>, sqlx::Error> {
-        let mut full_responses: Vec<FullResponse> = Vec::new();
-        for response in responses {
-            let user = sqlx::query("SELECT * FROM users WHERE telegram_id = $1")
-                .bind(response.telegram_id)
-                .fetch_one(&self.pool)
-                .await?;
-            full_responses.push(FullResponse {
-                id: response.id,
-                speech_code: response.speech_code,
-                telegram_id: response.telegram_id,
-                username: user.get("username"),
-                first_name: user.get("first_name"),
-                last_name: user.get("last_name"),
-            });
+/// A conference or meetup grouping speech codes
+pub struct Event {
+    pub id: i32,
+    pub name: String,
+    pub active: bool,
+    pub archived: bool,
+}
+
+pub struct Admin {
+    pub telegram_id: i64,
+    pub role: Role,
+    pub username: Option<String>,
+}
+
+/// An embedded up-migration; versions are applied in ascending order
+pub struct Migration {
+    pub version: i32,
+    pub name: &'static str,
+    sql: &'static str,
+}
+
+macro_rules! migration {
+    ($version:expr, $dir:literal, $name:literal) => {
+        Migration {
+            version: $version,
+            name: $name,
+            sql: include_str!(concat!("migrations/", $dir, "/", $name, ".sql")),
         }
-        Ok(full_responses)
+    };
+}
+
+// Declared after `migration!` so that the backends can use it
+#[cfg(feature = "postgres")]
+mod postgres;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+mod rows;
+mod memory;
+
+/// Query surface of the bot, implemented by each storage backend
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Migrations that have not been applied to the database yet; does not modify the database
+    async fn pending_migrations(&self) -> Result<Vec<&'static Migration>>;
+
+    /// Applies all pending migrations in a single transaction and returns them
+    async fn migrate(&self) -> Result<Vec<&'static Migration>>;
+
+    /// Creates or refreshes the profile and records the current username in the history
+    async fn upsert_user(&self, user: &User) -> Result<()>;
+
+    async fn get_users(&self) -> Result<Vec<i64>>;
+
+    async fn get_profiles(&self) -> Result<Vec<User>>;
+
+    /// Adds a code to the active event, if there is one
+    async fn add_code(&self, code: &str) -> Result<()>;
+
+    async fn del_code(&self, code: &str) -> Result<()>;
+
+    async fn flush_responses(&self) -> Result<()>;
+
+    /// Deletes all codes along with all responses
+    async fn flush_codes(&self) -> Result<()>;
+
+    /// Returns codes of the given event, or all codes if `event` is `None`
+    async fn get_codes(&self, event: Option<i32>) -> Result<Vec<String>>;
+
+    async fn get_speech(&self, code: &str) -> Result<Option<Speech>>;
+
+    /// Enables a rotating check-in suffix, `None` makes the code static again; returns `false` if the code does not exist
+    async fn set_rotation(&self, code: &str, rotation: Option<(String, i32)>) -> Result<bool>;
+
+    /// Sets the check-in window in minutes around the schedule, `None` removes it; returns `false` if the code does not exist
+    async fn set_checkin_window(&self, code: &str, window: Option<(i32, i32)>) -> Result<bool>;
+
+    /// Forces check-ins open or closed, `None` returns to the window; returns `false` if the code does not exist
+    async fn set_checkin_override(&self, code: &str, open: Option<bool>) -> Result<bool>;
+
+    /// Sets a metadata field of a speech, an empty value clears it; returns `false` if the code does not exist
+    async fn set_speech_field(&self, code: &str, field: SpeechField, value: &str) -> Result<bool>;
+
+    /// Returns `false` if the code does not exist
+    async fn set_speech_schedule(&self, code: &str, starts_at: Option<DateTime<Utc>>, ends_at: Option<DateTime<Utc>>) -> Result<bool>;
+
+    /// Codes of archived events no longer accept check-ins
+    async fn is_code_allowed(&self, code: &str) -> Result<bool>;
+
+    /// Records a check-in; does nothing if the user has already checked in for the code
+    async fn insert(&self, response: Response) -> Result<()>;
+
+    async fn get_users_by_code(&self, code: String) -> Result<Vec<i64>>;
+
+    async fn get_users_by_event(&self, event: i32) -> Result<Vec<i64>>;
+
+    async fn get_by_user(&self, user_id: i64) -> Result<Vec<Response>>;
+
+    /// Check-ins joined with their user, talk and rating in a single query, streamed row by row
+    /// in the order of codes and then check-ins; users without a profile get empty names
+    fn filtered_responses(&self, scope: ResponseScope, filter: ExportFilter) -> BoxStream<'_, Result<FullResponse>>;
+
+    fn full_responses(&self, scope: ResponseScope) -> BoxStream<'_, Result<FullResponse>> {
+        self.filtered_responses(scope, ExportFilter::default())
     }
 
-    pub async fn get_responses_by_username(&self, username: String) -> Result<Vec<Response>, sqlx::Error> {
-        let mut responses: Vec<Response> = Vec::new();
-        let telegram_id = sqlx::query("SELECT telegram_id FROM users where username = $1")
-            .bind(username)
-            .fetch_one(&self.pool)
-            .await?;
-        let telegram_id: i32 = telegram_id.get("telegram_id");
-        let mut rows = sqlx::query("SELECT * FROM responses WHERE telegram_id = $1")
-            .bind(telegram_id)
-            .fetch(&self.pool);
-
-        while let Some(row) = rows.try_next().await? {
-            responses.push(Response {
-                id: row.get("id"),
-                speech_code: row.get("speech_code"),
-                telegram_id: row.get("telegram_id"),
-            });
+    /// Results of everyone who checked in, sorted by label; `with_idle` adds registered users
+    /// without check-ins, limited to the filter's users if it has any
+    async fn get_all_username_results(&self, event: Option<i32>, filter: ExportFilter, with_idle: bool) -> Result<Vec<UsernameResult>> {
+        let mut results: HashMap<i64, UsernameResult> = HashMap::new();
+        if with_idle {
+            let profiles = self.get_profiles().await?;
+            for user in profiles.into_iter().filter(|user| filter.users.is_empty() || filter.users.contains(&user.telegram_id)) {
+                results.insert(user.telegram_id, UsernameResult {
+                    user,
+                    responses: Vec::new(),
+                });
+            }
         }
-        Ok(responses)
+        let scope = match event {
+            Some(event) => ResponseScope::Event(Some(event)),
+            None => ResponseScope::All,
+        };
+        let mut responses = self.filtered_responses(scope, filter);
+        while let Some(response) = responses.try_next().await? {
+            // Check-ins of users without a profile still count, under their id
+            results.entry(response.telegram_id)
+                .or_insert_with(|| UsernameResult {
+                    user: User {
+                        telegram_id: response.telegram_id,
+                        username: response.username.clone(),
+                        first_name: response.first_name.clone(),
+                        last_name: response.last_name.clone(),
+                    },
+                    responses: Vec::new(),
+                })
+                .responses.push(response);
+        }
+
+        let mut results = results.into_values().collect::<Vec<UsernameResult>>();
+        results.sort_by_cached_key(|result| (result.user.label().to_lowercase(), result.user.telegram_id));
+        Ok(results)
     }
 
-    pub async fn get_users_by_code(&self, code: String) -> Result<Vec<i32>, sqlx::Error> {
-        let mut users: Vec<i32> = Vec::new();
-        let mut rows = sqlx::query("SELECT telegram_id FROM responses WHERE speech_code = $1")
-            .bind(code)
-            .fetch(&self.pool);
+    async fn is_feedback_open(&self, code: &str) -> Result<bool>;
+
+    /// Returns `false` if the code does not exist
+    async fn set_feedback_open(&self, code: &str, open: bool) -> Result<bool>;
+
+    /// Sets or replaces the rating of a user's response; returns `false` if there is no such response
+    async fn set_rating(&self, telegram_id: i64, code: &str, rating: i32) -> Result<bool>;
+
+    async fn get_rating_summary(&self, code: &str) -> Result<RatingSummary>;
 
-        while let Some(row) = rows.try_next().await? {
-            users.push(row.get("telegram_id"));
+    async fn get_rating_summaries(&self, event: Option<i32>) -> Result<Vec<RatingSummary>> {
+        let mut summaries: Vec<RatingSummary> = Vec::new();
+        for code in self.get_codes(event).await? {
+            summaries.push(self.get_rating_summary(&code).await?);
         }
+        Ok(summaries)
+    }
+
+    /// Check-ins of the codes of an event, or of all codes if `None`, counted in the database
+    async fn get_stats(&self, event: Option<i32>, recent_since: DateTime<Utc>) -> Result<Stats>;
+
+    /// Number of check-ins for each minute with at least one, in chronological order
+    async fn get_timeline(&self, code: &str) -> Result<Vec<(DateTime<Utc>, i64)>>;
+
+    async fn has_response(&self, telegram_id: i64, code: &str) -> Result<bool>;
+
+    /// Sets or replaces the comment of a user's response; returns `false` if there is no such response
+    async fn set_comment(&self, telegram_id: i64, code: &str, text: &str) -> Result<bool>;
+
+    async fn get_comment(&self, telegram_id: i64, code: &str) -> Result<Option<String>>;
+
+    /// Returns `false` if there was no comment to delete
+    async fn delete_comment(&self, telegram_id: i64, code: &str) -> Result<bool>;
+
+    async fn get_comments_by_code(&self, code: &str) -> Result<Vec<Comment>>;
+
+    /// Appends a question to the survey of a code and returns its position
+    async fn add_question(&self, code: &str, kind: QuestionKind, text: &str, options: &[String]) -> Result<i32>;
+
+    /// Returns `false` if there is no such question
+    async fn del_question(&self, code: &str, position: i32) -> Result<bool>;
+
+    async fn get_questions(&self, code: &str) -> Result<Vec<Question>>;
 
-        Ok(users)
+    /// Sets or replaces a survey answer; returns `false` if the user has not checked in for the code
+    async fn set_answer(&self, question_id: i32, telegram_id: i64, code: &str, answer: &str) -> Result<bool>;
+
+    async fn get_survey_answers(&self, code: &str) -> Result<Vec<SurveyAnswers>>;
+
+    /// Returns `false` if an event with this name already exists
+    async fn add_event(&self, name: &str) -> Result<bool>;
+
+    async fn get_events(&self) -> Result<Vec<Event>>;
+
+    async fn get_event(&self, name: &str) -> Result<Option<Event>>;
+
+    /// Makes the event active and deactivates the previous one
+    async fn set_active_event(&self, event: i32) -> Result<()>;
+
+    /// Archived events keep their responses, but their codes no longer accept check-ins
+    async fn archive_event(&self, event: i32) -> Result<()>;
+
+    /// Returns `false` if the code does not exist
+    async fn set_code_event(&self, code: &str, event: i32) -> Result<bool>;
+
+    /// Case-insensitive; past usernames resolve too, the most recent holder of a handle wins
+    async fn get_user_id_by_username(&self, username: &str) -> Result<Option<i64>>;
+
+    async fn get_role(&self, telegram_id: i64) -> Result<Option<Role>>;
+
+    async fn set_role(&self, telegram_id: i64, role: Role) -> Result<()>;
+
+    /// Returns `false` if the user was not an admin
+    async fn revoke_admin(&self, telegram_id: i64) -> Result<bool>;
+
+    async fn count_owners(&self) -> Result<i64>;
+
+    async fn get_admins(&self) -> Result<Vec<Admin>>;
+
+    /// Deletes responses whose code is not in the allowed codes list
+    async fn flush_responses_with_unknown_codes(&self) -> Result<()>;
+
+    /// Users who checked in for at least `min_talks` of `codes`, or of any allowed codes if empty,
+    /// sorted by id; users banned from raffles and past winners are left out
+    async fn get_raffle_candidates(&self, min_talks: i32, codes: &[String]) -> Result<Vec<i64>>;
+
+    /// Stores a draw with its winners and returns its number
+    async fn add_raffle(&self, raffle: &Raffle) -> Result<i32>;
+
+    /// Returns `false` if the user was already banned
+    async fn ban_from_raffles(&self, telegram_id: i64) -> Result<bool>;
+
+    /// Returns `false` if the user was not banned
+    async fn unban_from_raffles(&self, telegram_id: i64) -> Result<bool>;
+}
+
+/// Storage backend chosen by the scheme of the database URL
+pub struct Database {
+    storage: Box<dyn Storage>,
+}
+
+impl Database {
+    /// Connects to `postgres://` or `sqlite:` URLs, depending on the enabled features
+    pub async fn new(database_url: &str) -> Result<Self> {
+        let scheme = database_url.split(':').next().unwrap_or_default();
+        let storage: Box<dyn Storage> = match scheme {
+            #[cfg(feature = "postgres")]
+            "postgres" | "postgresql" => Box::new(postgres::PgStorage::new(database_url).await?),
+            #[cfg(feature = "sqlite")]
+            "sqlite" => Box::new(sqlite::SqliteStorage::new(database_url).await?),
+            _ => return Err(Error::Config(format!("unsupported database URL scheme `{}`", scheme))),
+        };
+
+        Ok(Self { storage })
     }
 
-    pub async fn get_by_user(&self, user_id: i32) -> Result<Vec<Response>, sqlx::Error> {
-        let mut responses: Vec<Response> = Vec::new();
-        let mut rows = sqlx::query("SELECT * FROM responses WHERE telegram_id = $1")
-            .bind(user_id)
-            .fetch(&self.pool);
-
-        while let Some(row) = rows.try_next().await? {
-            responses.push(Response {
-                id: Some(row.get(0)),
-                speech_code: row.get(1),
-                telegram_id: row.get(2),
-            });
-        }
+    /// Empty storage that lives as long as the process, for demos and tests
+    pub fn memory() -> Self {
+        Self { storage: Box::new(memory::MemoryStorage::new()) }
+    }
+}
+
+impl Deref for Database {
+    type Target = dyn Storage;
 
-        Ok(responses)
+    fn deref(&self) -> &Self::Target {
+        self.storage.as_ref()
     }
+}
 
-    pub async fn get_by_code(&self, code: String) -> Result<Vec<Response>, sqlx::Error> {
-        let mut responses: Vec<Response> = Vec::new();
-        let mut rows = sqlx::query("SELECT * FROM responses WHERE speech_code = $1")
-            .bind(code)
-            .fetch(&self.pool);
-
-        while let Some(row) = rows.try_next().await? {
-            responses.push(Response {
-                id: Some(row.get(0)),
-                speech_code: row.get(1),
-                telegram_id: row.get(2),
-            });
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The memory backend and, with the `sqlite` feature, a fresh SQLite file, so that both answer the same
+    async fn backends() -> Vec<(&'static str, Database)> {
+        let backends = vec![
+            ("memory", Database::memory()),
+            #[cfg(feature = "sqlite")]
+            ("sqlite", sqlite_file().await),
+        ];
+        for (_, db) in &backends {
+            db.migrate().await.unwrap();
         }
+        backends
+    }
 
-        Ok(responses)
+    /// An empty database in a file of its own, since tests run in parallel
+    #[cfg(feature = "sqlite")]
+    async fn sqlite_file() -> Database {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        static FILES: AtomicUsize = AtomicUsize::new(0);
+        let name = format!("livefeedback-{}-{}.db", std::process::id(), FILES.fetch_add(1, Ordering::Relaxed));
+        let path = std::env::temp_dir().join(name);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+        Database::new(&format!("sqlite://{}", path.display())).await.unwrap()
     }
 
-    pub async fn get_by_telegram_id(&self, telegram_id: i32) -> Result<Vec<FullResponse>, sqlx::Error> {
-        let mut responses: Vec<FullResponse> = Vec::new();
-        let mut rows = sqlx::query("SELECT * FROM responses WHERE telegram_id = $1")
-            .bind(telegram_id)
-            .fetch(&self.pool);
-
-        while let Some(row) = rows.try_next().await? {
-            let user = sqlx::query("SELECT * FROM users WHERE telegram_id = $1")
-                .bind(telegram_id)
-                .fetch_one(&self.pool)
-                .await?;
-            responses.push(FullResponse {
-                id: row.get("id"),
-                speech_code: row.get("speech_code"),
-                telegram_id: row.get("telegram_id"),
-                username: user.get("username"),
-                first_name: user.get("first_name"),
-                last_name: user.get("last_name"),
-            });
+    fn response(code: &str, telegram_id: i64) -> Response {
+        Response {
+            id: None,
+            speech_code: code.to_string(),
+            telegram_id,
+            rating: None,
+            speech_title: None,
+            created_at: None,
         }
+    }
 
-        Ok(responses)
+    async fn codes_of(db: &Database, telegram_id: i64) -> Vec<String> {
+        let mut codes = db.get_by_user(telegram_id).await.unwrap()
+            .into_iter()
+            .map(|response| response.speech_code)
+            .collect::<Vec<String>>();
+        codes.sort();
+        codes
     }
 
-    pub async fn get_all_code_results(&self) -> Result<Vec<CodeResult>, sqlx::Error> {
-        // Get results from all allowed codes in the database
-        let mut code_results: Vec<CodeResult> = Vec::new();
-        let codes = self.get_codes().await?;
-        for code in codes {
-            let responses = self.get_by_code(code.clone()).await?;
-            let mut users: Vec<FullResponse> = Vec::new();
-            for response in responses {
-                let user = sqlx::query("SELECT * FROM users WHERE telegram_id = $1")
-                    .bind(response.telegram_id)
-                    .fetch_one(&self.pool)
-                    .await?;
-                users.push(FullResponse {
-                    id: response.id,
-                    speech_code: response.speech_code,
-                    telegram_id: response.telegram_id,
-                    username: user.get("username"),
-                    first_name: user.get("first_name"),
-                    last_name: user.get("last_name"),
-                });
+    #[tokio::test]
+    async fn check_ins_are_unique_per_user_and_code() {
+        for (backend, db) in backends().await {
+            db.add_code("A1").await.unwrap();
+            db.add_code("A1").await.unwrap();
+            db.add_code("B2").await.unwrap();
+            for (code, telegram_id) in [("A1", 1), ("A1", 1), ("B2", 1), ("A1", 2)] {
+                db.insert(response(code, telegram_id)).await.unwrap();
             }
-            code_results.push(CodeResult {
-                speech_code: code,
-                responses: users,
-            });
+
+            assert_eq!(db.get_codes(None).await.unwrap(), ["A1", "B2"], "{}", backend);
+            assert_eq!(codes_of(&db, 1).await, ["A1", "B2"], "{}", backend);
+            let mut users = db.get_users_by_code("A1".to_string()).await.unwrap();
+            users.sort();
+            assert_eq!(users, [1, 2], "{}", backend);
         }
-        Ok(code_results)
     }
 
-    pub async fn get_all_username_results(&self) -> Result<Vec<UsernameResult>, sqlx::Error> {
-        // Get results from all usernames in the database
-        let mut username_results: Vec<UsernameResult> = Vec::new();
-        let telegram_ids = self.get_users().await?;
-        for telegram_id in telegram_ids {
-            let responses = self.get_by_telegram_id(telegram_id).await?;
-            let username = responses[0].username.clone();
-            username_results.push(UsernameResult {
-                telegram_id,
-                username,
-                responses,
-            });
+    #[tokio::test]
+    async fn flushing_codes_cascades_to_everything_about_them() {
+        for (backend, db) in backends().await {
+            db.add_code("A1").await.unwrap();
+            db.insert(response("A1", 1)).await.unwrap();
+            db.set_rating(1, "A1", 5).await.unwrap();
+            db.set_comment(1, "A1", "Спасибо").await.unwrap();
+            db.add_question("A1", QuestionKind::Text, "Что понравилось?", &[]).await.unwrap();
+            let question = db.get_questions("A1").await.unwrap()[0].id;
+            db.set_answer(question, 1, "A1", "Всё").await.unwrap();
+
+            db.flush_codes().await.unwrap();
+
+            assert!(db.get_codes(None).await.unwrap().is_empty(), "{}", backend);
+            assert!(codes_of(&db, 1).await.is_empty(), "{}", backend);
+            assert!(db.get_questions("A1").await.unwrap().is_empty(), "{}", backend);
+            assert!(db.get_comments_by_code("A1").await.unwrap().is_empty(), "{}", backend);
+            assert!(db.get_survey_answers("A1").await.unwrap().is_empty(), "{}", backend);
+            assert_eq!(db.get_rating_summary("A1").await.unwrap().count(), 0, "{}", backend);
+
+            // Nothing is left over for a code added again
+            db.add_code("A1").await.unwrap();
+            db.insert(response("A1", 1)).await.unwrap();
+            assert_eq!(db.get_by_user(1).await.unwrap()[0].rating, None, "{}", backend);
+            assert_eq!(db.get_comment(1, "A1").await.unwrap(), None, "{}", backend);
         }
-
-        Ok(username_results)
     }
 
-    pub async fn flush_responses_with_unknown_codes(&self) -> Result<(), sqlx::Error> {
-        // Strip all responses with codes that are not in the allowed codes list in the database
-        let codes = self.get_codes().await?;
-        let mut rows = sqlx::query("SELECT * FROM responses")
-            .fetch(&self.pool);
-
-        while let Some(row) = rows.try_next().await? {
-            let speech_code: String = row.get("speech_code");
-            if !codes.contains(&speech_code) {
-                sqlx::query("DELETE FROM responses WHERE id = $1")
-                    .bind(row.get::<i32, _>("id"))
-                    .execute(&self.pool)
-                    .await?;
-            }
+    #[tokio::test]
+    async fn comments_of_attendees_without_a_profile_are_listed() {
+        for (backend, db) in backends().await {
+            db.add_code("A1").await.unwrap();
+            db.insert(response("A1", 1)).await.unwrap();
+            db.set_comment(1, "A1", "Спасибо").await.unwrap();
+
+            let comments = db.get_comments_by_code("A1").await.unwrap();
+            assert_eq!(comments.len(), 1, "{}", backend);
+            assert_eq!(comments[0].text, "Спасибо", "{}", backend);
+            assert_eq!(comments[0].username, "", "{}", backend);
         }
+    }
 
-        Ok(())
+    #[tokio::test]
+    async fn answers_of_attendees_without_a_profile_are_listed() {
+        for (backend, db) in backends().await {
+            db.add_code("A1").await.unwrap();
+            db.insert(response("A1", 1)).await.unwrap();
+            db.add_question("A1", QuestionKind::Text, "Что понравилось?", &[]).await.unwrap();
+            let question = db.get_questions("A1").await.unwrap()[0].id;
+            db.set_answer(question, 1, "A1", "Всё").await.unwrap();
+
+            let answers = db.get_survey_answers("A1").await.unwrap();
+            assert_eq!(answers.len(), 1, "{}", backend);
+            assert_eq!(answers[0].answers[&question], "Всё", "{}", backend);
+            assert_eq!(answers[0].username, "", "{}", backend);
+        }
     }
-    // pub async fn get_all(&self) -> Result<Vec<Response>, sqlx::Error> {
-    //     let mut responses: Vec<Response> = Vec::new();
-    //     let mut rows = sqlx::query("SELECT * FROM responses")
-    //         .fetch(&self.pool);
 
-    //     while let Some(row) = rows.try_next().await {
-    //     }
+    #[tokio::test]
+    async fn flushing_unknown_codes_keeps_allowed_ones() {
+        for (backend, db) in backends().await {
+            // Nothing to flush yet
+            db.flush_responses_with_unknown_codes().await.unwrap();
 
-    //     Ok(vec![])
-    // }
-    }
\ No newline at end of file
+            db.add_code("A1").await.unwrap();
+            db.add_code("B2").await.unwrap();
+            for code in ["A1", "B2", "ZZ"] {
+                db.insert(response(code, 1)).await.unwrap();
+            }
+            db.set_rating(1, "ZZ", 1).await.unwrap();
+            // Responses of a deleted code become unknown
+            db.del_code("B2").await.unwrap();
+
+            db.flush_responses_with_unknown_codes().await.unwrap();
+
+            assert_eq!(codes_of(&db, 1).await, ["A1"], "{}", backend);
+            assert_eq!(db.get_rating_summary("ZZ").await.unwrap().count(), 0, "{}", backend);
+        }
+    }
+}
@@ -0,0 +1,699 @@
+use async_trait::async_trait;
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use futures::stream::{BoxStream, StreamExt};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Mutex;
+
+use crate::bot::error::{Error, Result};
+use super::{
+    Admin, CodeCheckIns, Comment, Event, ExportFilter, FullResponse, Migration, Question, QuestionKind, Raffle,
+    RatingSummary, Response, ResponseScope, Role, Speech, SpeechField, Stats, Storage, SurveyAnswers, User,
+};
+
+struct CodeRow {
+    speech: Speech,
+    feedback_open: bool,
+    event_id: Option<i32>,
+}
+
+struct ResponseRow {
+    id: i32,
+    speech_code: String,
+    telegram_id: i64,
+    created_at: DateTime<Utc>,
+}
+
+struct CommentRow {
+    text: String,
+    created_at: DateTime<Utc>,
+    updated_at: Option<DateTime<Utc>>,
+}
+
+struct QuestionRow {
+    speech_code: String,
+    question: Question,
+}
+
+#[derive(Default)]
+struct Tables {
+    users: BTreeMap<i64, User>,
+    /// Telegram id and username pairs, most recently seen last
+    usernames: Vec<(i64, String)>,
+    codes: Vec<CodeRow>,
+    responses: Vec<ResponseRow>,
+    /// Keyed by response id
+    ratings: HashMap<i32, i32>,
+    /// Keyed by response id
+    comments: HashMap<i32, CommentRow>,
+    questions: Vec<QuestionRow>,
+    /// Keyed by question and response id
+    answers: HashMap<(i32, i32), String>,
+    events: Vec<Event>,
+    admins: BTreeMap<i64, Role>,
+    raffle_bans: BTreeSet<i64>,
+    raffles: Vec<Raffle>,
+    last_response_id: i32,
+    last_question_id: i32,
+    last_event_id: i32,
+}
+
+impl Tables {
+    fn code_mut(&mut self, code: &str) -> Option<&mut CodeRow> {
+        self.codes.iter_mut().find(|row| row.speech.code == code)
+    }
+
+    fn user_id_by_username(&self, username: &str) -> Option<i64> {
+        self.usernames.iter().rev()
+            .find(|(_, seen)| seen.eq_ignore_ascii_case(username))
+            .map(|(telegram_id, _)| *telegram_id)
+    }
+
+    fn response_id(&self, telegram_id: i64, code: &str) -> Option<i32> {
+        self.responses.iter()
+            .find(|row| row.telegram_id == telegram_id && row.speech_code == code)
+            .map(|row| row.id)
+    }
+
+    fn to_response(&self, row: &ResponseRow) -> Response {
+        Response {
+            id: Some(row.id),
+            speech_code: row.speech_code.clone(),
+            telegram_id: row.telegram_id,
+            rating: self.ratings.get(&row.id).copied(),
+            speech_title: self.codes.iter()
+                .find(|code| code.speech.code == row.speech_code)
+                .and_then(|code| code.speech.title.clone()),
+            created_at: Some(row.created_at),
+        }
+    }
+
+    /// Users without a profile get empty names, like the SQL backends' `LEFT JOIN`
+    fn full_response(&self, response: Response) -> FullResponse {
+        let user = self.users.get(&response.telegram_id);
+        FullResponse {
+            id: response.id,
+            speech_code: response.speech_code,
+            telegram_id: response.telegram_id,
+            username: user.map(|user| user.username.clone()).unwrap_or_default(),
+            first_name: user.map(|user| user.first_name.clone()).unwrap_or_default(),
+            last_name: user.map(|user| user.last_name.clone()).unwrap_or_default(),
+            rating: response.rating,
+            speech_title: response.speech_title,
+            created_at: response.created_at,
+        }
+    }
+
+    /// Deletes the responses that do not match `keep`, cascading to their ratings, comments and answers
+    fn retain_responses(&mut self, keep: impl Fn(&ResponseRow) -> bool) {
+        self.responses.retain(|row| keep(row));
+        let ids = self.responses.iter().map(|row| row.id).collect::<Vec<i32>>();
+        self.ratings.retain(|id, _| ids.contains(id));
+        self.comments.retain(|id, _| ids.contains(id));
+        self.answers.retain(|(_, id), _| ids.contains(id));
+    }
+
+    /// Deletes the codes that do not match `keep`, cascading to their questions and answers
+    fn retain_codes(&mut self, keep: impl Fn(&CodeRow) -> bool) {
+        self.codes.retain(|row| keep(row));
+        let codes = self.codes.iter().map(|row| row.speech.code.clone()).collect::<Vec<String>>();
+        self.questions.retain(|row| codes.contains(&row.speech_code));
+        let ids = self.questions.iter().map(|row| row.question.id).collect::<Vec<i32>>();
+        self.answers.retain(|(id, _), _| ids.contains(id));
+    }
+}
+
+/// Keeps everything in memory and loses it on exit; behaves like the SQL backends
+/// including unique check-ins per user and code and cascading deletes
+#[derive(Default)]
+pub struct MemoryStorage {
+    tables: Mutex<Tables>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl Storage for MemoryStorage {
+    async fn pending_migrations(&self) -> Result<Vec<&'static Migration>> {
+        Ok(Vec::new())
+    }
+
+    async fn migrate(&self) -> Result<Vec<&'static Migration>> {
+        Ok(Vec::new())
+    }
+
+    async fn upsert_user(&self, user: &User) -> Result<()> {
+        let mut tables = self.tables.lock().unwrap();
+        tables.users.insert(user.telegram_id, User {
+            telegram_id: user.telegram_id,
+            username: user.username.clone(),
+            first_name: user.first_name.clone(),
+            last_name: user.last_name.clone(),
+        });
+        if !user.username.is_empty() {
+            tables.usernames.retain(|(telegram_id, seen)| *telegram_id != user.telegram_id || *seen != user.username);
+            tables.usernames.push((user.telegram_id, user.username.clone()));
+        }
+        Ok(())
+    }
+
+    async fn get_users(&self) -> Result<Vec<i64>> {
+        let tables = self.tables.lock().unwrap();
+        Ok(tables.users.keys().copied().collect())
+    }
+
+    async fn get_profiles(&self) -> Result<Vec<User>> {
+        let tables = self.tables.lock().unwrap();
+        Ok(tables.users.values()
+            .map(|user| User {
+                telegram_id: user.telegram_id,
+                username: user.username.clone(),
+                first_name: user.first_name.clone(),
+                last_name: user.last_name.clone(),
+            })
+            .collect())
+    }
+
+    async fn add_code(&self, code: &str) -> Result<()> {
+        let mut tables = self.tables.lock().unwrap();
+        if tables.code_mut(code).is_some() {
+            return Ok(());
+        }
+        let event_id = tables.events.iter().find(|event| event.active).map(|event| event.id);
+        tables.codes.push(CodeRow {
+            speech: Speech {
+                code: code.to_string(),
+                title: None,
+                speaker: None,
+                room: None,
+                starts_at: None,
+                ends_at: None,
+                window: None,
+                checkin_override: None,
+                rotation: None,
+            },
+            feedback_open: true,
+            event_id,
+        });
+        Ok(())
+    }
+
+    async fn del_code(&self, code: &str) -> Result<()> {
+        let mut tables = self.tables.lock().unwrap();
+        tables.retain_codes(|row| row.speech.code != code);
+        Ok(())
+    }
+
+    async fn flush_responses(&self) -> Result<()> {
+        let mut tables = self.tables.lock().unwrap();
+        tables.retain_responses(|_| false);
+        Ok(())
+    }
+
+    async fn flush_codes(&self) -> Result<()> {
+        let mut tables = self.tables.lock().unwrap();
+        tables.retain_codes(|_| false);
+        tables.retain_responses(|_| false);
+        Ok(())
+    }
+
+    async fn get_codes(&self, event: Option<i32>) -> Result<Vec<String>> {
+        let tables = self.tables.lock().unwrap();
+        Ok(tables.codes.iter()
+            .filter(|row| event.is_none() || row.event_id == event)
+            .map(|row| row.speech.code.clone())
+            .collect())
+    }
+
+    async fn get_speech(&self, code: &str) -> Result<Option<Speech>> {
+        let mut tables = self.tables.lock().unwrap();
+        Ok(tables.code_mut(code).map(|row| row.speech.clone()))
+    }
+
+    async fn set_rotation(&self, code: &str, rotation: Option<(String, i32)>) -> Result<bool> {
+        let mut tables = self.tables.lock().unwrap();
+        Ok(tables.code_mut(code).map(|row| row.speech.rotation = rotation).is_some())
+    }
+
+    async fn set_checkin_window(&self, code: &str, window: Option<(i32, i32)>) -> Result<bool> {
+        let mut tables = self.tables.lock().unwrap();
+        Ok(tables.code_mut(code).map(|row| row.speech.window = window).is_some())
+    }
+
+    async fn set_checkin_override(&self, code: &str, open: Option<bool>) -> Result<bool> {
+        let mut tables = self.tables.lock().unwrap();
+        Ok(tables.code_mut(code).map(|row| row.speech.checkin_override = open).is_some())
+    }
+
+    async fn set_speech_field(&self, code: &str, field: SpeechField, value: &str) -> Result<bool> {
+        let mut tables = self.tables.lock().unwrap();
+        let Some(row) = tables.code_mut(code) else {
+            return Ok(false);
+        };
+        let value = Some(value.to_string()).filter(|value| !value.is_empty());
+        match field {
+            SpeechField::Title => row.speech.title = value,
+            SpeechField::Speaker => row.speech.speaker = value,
+            SpeechField::Room => row.speech.room = value,
+        }
+        Ok(true)
+    }
+
+    async fn set_speech_schedule(&self, code: &str, starts_at: Option<DateTime<Utc>>, ends_at: Option<DateTime<Utc>>) -> Result<bool> {
+        let mut tables = self.tables.lock().unwrap();
+        let Some(row) = tables.code_mut(code) else {
+            return Ok(false);
+        };
+        row.speech.starts_at = starts_at;
+        row.speech.ends_at = ends_at;
+        Ok(true)
+    }
+
+    async fn is_code_allowed(&self, code: &str) -> Result<bool> {
+        let tables = self.tables.lock().unwrap();
+        let Some(row) = tables.codes.iter().find(|row| row.speech.code == code) else {
+            return Ok(false);
+        };
+        let archived = tables.events.iter().any(|event| Some(event.id) == row.event_id && event.archived);
+        Ok(!archived)
+    }
+
+    async fn insert(&self, response: Response) -> Result<()> {
+        let mut tables = self.tables.lock().unwrap();
+        if tables.response_id(response.telegram_id, &response.speech_code).is_some() {
+            return Ok(());
+        }
+        tables.last_response_id += 1;
+        let id = tables.last_response_id;
+        tables.responses.push(ResponseRow {
+            id,
+            speech_code: response.speech_code,
+            telegram_id: response.telegram_id,
+            created_at: Utc::now(),
+        });
+        Ok(())
+    }
+
+    async fn get_users_by_code(&self, code: String) -> Result<Vec<i64>> {
+        let tables = self.tables.lock().unwrap();
+        Ok(tables.responses.iter()
+            .filter(|row| row.speech_code == code)
+            .map(|row| row.telegram_id)
+            .collect())
+    }
+
+    async fn get_users_by_event(&self, event: i32) -> Result<Vec<i64>> {
+        let tables = self.tables.lock().unwrap();
+        let mut users: Vec<i64> = tables.responses.iter()
+            .filter(|row| tables.codes.iter().any(|code| code.speech.code == row.speech_code && code.event_id == Some(event)))
+            .map(|row| row.telegram_id)
+            .collect();
+        users.sort_unstable();
+        users.dedup();
+        Ok(users)
+    }
+
+    async fn get_by_user(&self, user_id: i64) -> Result<Vec<Response>> {
+        let tables = self.tables.lock().unwrap();
+        Ok(tables.responses.iter()
+            .filter(|row| row.telegram_id == user_id)
+            .map(|row| tables.to_response(row))
+            .collect())
+    }
+
+    fn filtered_responses(&self, scope: ResponseScope, filter: ExportFilter) -> BoxStream<'_, Result<FullResponse>> {
+        let tables = self.tables.lock().unwrap();
+        let (code, telegram_id, known_codes, event) = scope.into_params();
+        let mut rows = tables.responses.iter()
+            .filter(|row| code.as_ref().is_none_or(|code| row.speech_code == *code))
+            .filter(|row| telegram_id.is_none_or(|telegram_id| row.telegram_id == telegram_id))
+            .filter(|row| filter.matches(&row.speech_code, row.telegram_id, row.created_at))
+            .map(|row| (tables.codes.iter().position(|code| code.speech.code == row.speech_code), row))
+            .filter(|(position, _)| !known_codes || position.is_some())
+            .filter(|(position, _)| event.is_none() || position.is_some_and(|position| tables.codes[position].event_id == event))
+            .collect::<Vec<_>>();
+        // Unknown codes last, as in the SQL backends
+        rows.sort_by_key(|(position, row)| (position.is_none(), *position, row.id));
+        let responses = rows.into_iter()
+            .map(|(_, row)| Ok(tables.full_response(tables.to_response(row))))
+            .collect::<Vec<_>>();
+        futures::stream::iter(responses).boxed()
+    }
+
+    async fn is_feedback_open(&self, code: &str) -> Result<bool> {
+        let mut tables = self.tables.lock().unwrap();
+        Ok(tables.code_mut(code).map(|row| row.feedback_open).unwrap_or(false))
+    }
+
+    async fn set_feedback_open(&self, code: &str, open: bool) -> Result<bool> {
+        let mut tables = self.tables.lock().unwrap();
+        Ok(tables.code_mut(code).map(|row| row.feedback_open = open).is_some())
+    }
+
+    async fn set_rating(&self, telegram_id: i64, code: &str, rating: i32) -> Result<bool> {
+        let mut tables = self.tables.lock().unwrap();
+        let Some(id) = tables.response_id(telegram_id, code) else {
+            return Ok(false);
+        };
+        tables.ratings.insert(id, rating);
+        Ok(true)
+    }
+
+    async fn get_rating_summary(&self, code: &str) -> Result<RatingSummary> {
+        let mut tables = self.tables.lock().unwrap();
+        let speech_title = tables.code_mut(code).and_then(|row| row.speech.title.clone());
+        let mut summary = RatingSummary {
+            speech_code: code.to_string(),
+            speech_title,
+            distribution: [0; 5],
+        };
+        for row in tables.responses.iter().filter(|row| row.speech_code == code) {
+            if let Some(rating) = tables.ratings.get(&row.id) {
+                summary.distribution[(rating - 1) as usize] += 1;
+            }
+        }
+        Ok(summary)
+    }
+
+    async fn get_stats(&self, event: Option<i32>, recent_since: DateTime<Utc>) -> Result<Stats> {
+        let tables = self.tables.lock().unwrap();
+        let codes = tables.codes.iter()
+            .filter(|code| event.is_none() || code.event_id == event)
+            .collect::<Vec<&CodeRow>>();
+        let responses = tables.responses.iter()
+            .filter(|row| codes.iter().any(|code| code.speech.code == row.speech_code))
+            .collect::<Vec<&ResponseRow>>();
+
+        let mut talks: HashMap<i64, i64> = HashMap::new();
+        for row in &responses {
+            *talks.entry(row.telegram_id).or_default() += 1;
+        }
+        let mut talks_per_attendee: BTreeMap<i64, i64> = BTreeMap::new();
+        for count in talks.into_values() {
+            *talks_per_attendee.entry(count).or_default() += 1;
+        }
+
+        Ok(Stats {
+            codes: codes.iter()
+                .map(|code| CodeCheckIns {
+                    speech_code: code.speech.code.clone(),
+                    speech_title: code.speech.title.clone(),
+                    check_ins: responses.iter().filter(|row| row.speech_code == code.speech.code).count() as i64,
+                })
+                .collect(),
+            talks_per_attendee: talks_per_attendee.into_iter().collect(),
+            recent: responses.iter().filter(|row| row.created_at >= recent_since).count() as i64,
+        })
+    }
+
+    async fn get_timeline(&self, code: &str) -> Result<Vec<(DateTime<Utc>, i64)>> {
+        let tables = self.tables.lock().unwrap();
+        let mut timeline: BTreeMap<DateTime<Utc>, i64> = BTreeMap::new();
+        for row in tables.responses.iter().filter(|row| row.speech_code == code) {
+            let minute = row.created_at.duration_trunc(TimeDelta::minutes(1)).unwrap_or(row.created_at);
+            *timeline.entry(minute).or_default() += 1;
+        }
+        Ok(timeline.into_iter().collect())
+    }
+
+    async fn has_response(&self, telegram_id: i64, code: &str) -> Result<bool> {
+        let tables = self.tables.lock().unwrap();
+        Ok(tables.response_id(telegram_id, code).is_some())
+    }
+
+    async fn set_comment(&self, telegram_id: i64, code: &str, text: &str) -> Result<bool> {
+        let mut tables = self.tables.lock().unwrap();
+        let Some(id) = tables.response_id(telegram_id, code) else {
+            return Ok(false);
+        };
+        match tables.comments.get_mut(&id) {
+            Some(comment) => {
+                comment.text = text.to_string();
+                comment.updated_at = Some(Utc::now());
+            },
+            None => {
+                tables.comments.insert(id, CommentRow {
+                    text: text.to_string(),
+                    created_at: Utc::now(),
+                    updated_at: None,
+                });
+            },
+        }
+        Ok(true)
+    }
+
+    async fn get_comment(&self, telegram_id: i64, code: &str) -> Result<Option<String>> {
+        let tables = self.tables.lock().unwrap();
+        Ok(tables.response_id(telegram_id, code)
+            .and_then(|id| tables.comments.get(&id))
+            .map(|comment| comment.text.clone()))
+    }
+
+    async fn delete_comment(&self, telegram_id: i64, code: &str) -> Result<bool> {
+        let mut tables = self.tables.lock().unwrap();
+        let Some(id) = tables.response_id(telegram_id, code) else {
+            return Ok(false);
+        };
+        Ok(tables.comments.remove(&id).is_some())
+    }
+
+    async fn get_comments_by_code(&self, code: &str) -> Result<Vec<Comment>> {
+        let tables = self.tables.lock().unwrap();
+        let mut comments: Vec<Comment> = Vec::new();
+        for row in tables.responses.iter().filter(|row| row.speech_code == code) {
+            let Some(comment) = tables.comments.get(&row.id) else {
+                continue;
+            };
+            let user = tables.users.get(&row.telegram_id);
+            comments.push(Comment {
+                speech_code: row.speech_code.clone(),
+                speech_title: tables.to_response(row).speech_title,
+                checked_in_at: Some(row.created_at),
+                telegram_id: row.telegram_id,
+                username: user.map(|user| user.username.clone()).unwrap_or_default(),
+                first_name: user.map(|user| user.first_name.clone()).unwrap_or_default(),
+                last_name: user.map(|user| user.last_name.clone()).unwrap_or_default(),
+                text: comment.text.clone(),
+                created_at: comment.created_at,
+                updated_at: comment.updated_at,
+            });
+        }
+        comments.sort_by_key(|comment| comment.created_at);
+        Ok(comments)
+    }
+
+    async fn add_question(&self, code: &str, kind: QuestionKind, text: &str, options: &[String]) -> Result<i32> {
+        let mut tables = self.tables.lock().unwrap();
+        // Same as the foreign key violation of the SQL backends
+        if tables.code_mut(code).is_none() {
+            return Err(Error::Database(sqlx::Error::RowNotFound));
+        }
+        let position = tables.questions.iter()
+            .filter(|row| row.speech_code == code)
+            .map(|row| row.question.position)
+            .max()
+            .unwrap_or(0) + 1;
+        tables.last_question_id += 1;
+        let id = tables.last_question_id;
+        tables.questions.push(QuestionRow {
+            speech_code: code.to_string(),
+            question: Question {
+                id,
+                position,
+                kind,
+                text: text.to_string(),
+                options: options.to_vec(),
+            },
+        });
+        Ok(position)
+    }
+
+    async fn del_question(&self, code: &str, position: i32) -> Result<bool> {
+        let mut tables = self.tables.lock().unwrap();
+        let Some(index) = tables.questions.iter().position(|row| row.speech_code == code && row.question.position == position) else {
+            return Ok(false);
+        };
+        let id = tables.questions.remove(index).question.id;
+        tables.answers.retain(|(question_id, _), _| *question_id != id);
+        Ok(true)
+    }
+
+    async fn get_questions(&self, code: &str) -> Result<Vec<Question>> {
+        let tables = self.tables.lock().unwrap();
+        let mut questions: Vec<Question> = tables.questions.iter()
+            .filter(|row| row.speech_code == code)
+            .map(|row| row.question.clone())
+            .collect();
+        questions.sort_by_key(|question| question.position);
+        Ok(questions)
+    }
+
+    async fn set_answer(&self, question_id: i32, telegram_id: i64, code: &str, answer: &str) -> Result<bool> {
+        let mut tables = self.tables.lock().unwrap();
+        let Some(id) = tables.response_id(telegram_id, code) else {
+            return Ok(false);
+        };
+        tables.answers.insert((question_id, id), answer.to_string());
+        Ok(true)
+    }
+
+    async fn get_survey_answers(&self, code: &str) -> Result<Vec<SurveyAnswers>> {
+        let tables = self.tables.lock().unwrap();
+        let mut survey_answers: Vec<SurveyAnswers> = Vec::new();
+        for row in tables.responses.iter().filter(|row| row.speech_code == code) {
+            let answers: HashMap<i32, String> = tables.answers.iter()
+                .filter(|((_, response_id), _)| *response_id == row.id)
+                .map(|((question_id, _), answer)| (*question_id, answer.clone()))
+                .collect();
+            if answers.is_empty() {
+                continue;
+            }
+            let user = tables.users.get(&row.telegram_id);
+            survey_answers.push(SurveyAnswers {
+                telegram_id: row.telegram_id,
+                checked_in_at: Some(row.created_at),
+                username: user.map(|user| user.username.clone()).unwrap_or_default(),
+                first_name: user.map(|user| user.first_name.clone()).unwrap_or_default(),
+                last_name: user.map(|user| user.last_name.clone()).unwrap_or_default(),
+                answers,
+            });
+        }
+        survey_answers.sort_by_key(|attendee| attendee.telegram_id);
+        Ok(survey_answers)
+    }
+
+    async fn add_event(&self, name: &str) -> Result<bool> {
+        let mut tables = self.tables.lock().unwrap();
+        if tables.events.iter().any(|event| event.name == name) {
+            return Ok(false);
+        }
+        tables.last_event_id += 1;
+        let id = tables.last_event_id;
+        tables.events.push(Event {
+            id,
+            name: name.to_string(),
+            active: false,
+            archived: false,
+        });
+        Ok(true)
+    }
+
+    async fn get_events(&self) -> Result<Vec<Event>> {
+        let tables = self.tables.lock().unwrap();
+        Ok(tables.events.iter()
+            .map(|event| Event {
+                id: event.id,
+                name: event.name.clone(),
+                active: event.active,
+                archived: event.archived,
+            })
+            .collect())
+    }
+
+    async fn get_event(&self, name: &str) -> Result<Option<Event>> {
+        Ok(self.get_events().await?.into_iter().find(|event| event.name == name))
+    }
+
+    async fn set_active_event(&self, event: i32) -> Result<()> {
+        let mut tables = self.tables.lock().unwrap();
+        for row in tables.events.iter_mut() {
+            row.active = row.id == event;
+            if row.active {
+                row.archived = false;
+            }
+        }
+        Ok(())
+    }
+
+    async fn archive_event(&self, event: i32) -> Result<()> {
+        let mut tables = self.tables.lock().unwrap();
+        if let Some(row) = tables.events.iter_mut().find(|row| row.id == event) {
+            row.archived = true;
+            row.active = false;
+        }
+        Ok(())
+    }
+
+    async fn set_code_event(&self, code: &str, event: i32) -> Result<bool> {
+        let mut tables = self.tables.lock().unwrap();
+        Ok(tables.code_mut(code).map(|row| row.event_id = Some(event)).is_some())
+    }
+
+    async fn get_user_id_by_username(&self, username: &str) -> Result<Option<i64>> {
+        let tables = self.tables.lock().unwrap();
+        Ok(tables.user_id_by_username(username))
+    }
+
+    async fn get_role(&self, telegram_id: i64) -> Result<Option<Role>> {
+        let tables = self.tables.lock().unwrap();
+        Ok(tables.admins.get(&telegram_id).copied())
+    }
+
+    async fn set_role(&self, telegram_id: i64, role: Role) -> Result<()> {
+        let mut tables = self.tables.lock().unwrap();
+        tables.admins.insert(telegram_id, role);
+        Ok(())
+    }
+
+    async fn revoke_admin(&self, telegram_id: i64) -> Result<bool> {
+        let mut tables = self.tables.lock().unwrap();
+        Ok(tables.admins.remove(&telegram_id).is_some())
+    }
+
+    async fn count_owners(&self) -> Result<i64> {
+        let tables = self.tables.lock().unwrap();
+        Ok(tables.admins.values().filter(|role| **role == Role::Owner).count() as i64)
+    }
+
+    async fn get_admins(&self) -> Result<Vec<Admin>> {
+        let tables = self.tables.lock().unwrap();
+        Ok(tables.admins.iter()
+            .map(|(telegram_id, role)| Admin {
+                telegram_id: *telegram_id,
+                role: *role,
+                username: tables.users.get(telegram_id).map(|user| user.username.clone()),
+            })
+            .collect())
+    }
+
+    async fn flush_responses_with_unknown_codes(&self) -> Result<()> {
+        let mut tables = self.tables.lock().unwrap();
+        let codes = tables.codes.iter().map(|row| row.speech.code.clone()).collect::<Vec<String>>();
+        tables.retain_responses(|row| codes.contains(&row.speech_code));
+        Ok(())
+    }
+
+    async fn get_raffle_candidates(&self, min_talks: i32, codes: &[String]) -> Result<Vec<i64>> {
+        let tables = self.tables.lock().unwrap();
+        let mut talks: BTreeMap<i64, BTreeSet<&str>> = BTreeMap::new();
+        for row in &tables.responses {
+            let allowed = tables.codes.iter().any(|code| code.speech.code == row.speech_code);
+            if allowed && (codes.is_empty() || codes.contains(&row.speech_code)) {
+                talks.entry(row.telegram_id).or_default().insert(&row.speech_code);
+            }
+        }
+        Ok(talks.into_iter()
+            .filter(|(_, talks)| talks.len() >= min_talks.max(0) as usize)
+            .map(|(telegram_id, _)| telegram_id)
+            .filter(|telegram_id| !tables.raffle_bans.contains(telegram_id))
+            .filter(|telegram_id| !tables.raffles.iter().any(|raffle| raffle.winners.contains(telegram_id)))
+            .collect())
+    }
+
+    async fn add_raffle(&self, raffle: &Raffle) -> Result<i32> {
+        let mut tables = self.tables.lock().unwrap();
+        tables.raffles.push(raffle.clone());
+        Ok(tables.raffles.len() as i32)
+    }
+
+    async fn ban_from_raffles(&self, telegram_id: i64) -> Result<bool> {
+        Ok(self.tables.lock().unwrap().raffle_bans.insert(telegram_id))
+    }
+
+    async fn unban_from_raffles(&self, telegram_id: i64) -> Result<bool> {
+        Ok(self.tables.lock().unwrap().raffle_bans.remove(&telegram_id))
+    }
+}
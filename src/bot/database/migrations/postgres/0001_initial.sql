@@ -0,0 +1,21 @@
+-- Schema created by Database::init before versioned migrations
+CREATE TABLE IF NOT EXISTS responses (
+    id SERIAL PRIMARY KEY,
+    speech_code VARCHAR(32) NOT NULL,
+    telegram_id INT NOT NULL,
+    UNIQUE (telegram_id, speech_code)
+);
+
+CREATE TABLE IF NOT EXISTS allowed_codes (
+    id SERIAL PRIMARY KEY,
+    code VARCHAR(32) NOT NULL,
+    UNIQUE (code)
+);
+
+CREATE TABLE IF NOT EXISTS users (
+    telegram_id INT PRIMARY KEY,
+    first_name VARCHAR(64) NOT NULL,
+    last_name VARCHAR(64) NOT NULL,
+    username VARCHAR(32) NOT NULL,
+    UNIQUE (telegram_id)
+);
@@ -0,0 +1,4 @@
+CREATE TABLE IF NOT EXISTS admins (
+    telegram_id INT PRIMARY KEY,
+    role VARCHAR(16) NOT NULL
+);
@@ -0,0 +1,6 @@
+ALTER TABLE allowed_codes ADD COLUMN IF NOT EXISTS feedback_open BOOLEAN NOT NULL DEFAULT TRUE;
+
+CREATE TABLE IF NOT EXISTS ratings (
+    response_id INT PRIMARY KEY REFERENCES responses (id) ON DELETE CASCADE,
+    rating INT NOT NULL CHECK (rating BETWEEN 1 AND 5)
+);
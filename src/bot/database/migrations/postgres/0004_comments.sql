@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS comments (
+    response_id INT PRIMARY KEY REFERENCES responses (id) ON DELETE CASCADE,
+    text TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ
+);
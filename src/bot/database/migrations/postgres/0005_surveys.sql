@@ -0,0 +1,17 @@
+CREATE TABLE IF NOT EXISTS questions (
+    id SERIAL PRIMARY KEY,
+    speech_code VARCHAR(32) NOT NULL REFERENCES allowed_codes (code) ON DELETE CASCADE,
+    position INT NOT NULL,
+    kind VARCHAR(16) NOT NULL,
+    text TEXT NOT NULL,
+    options TEXT[] NOT NULL DEFAULT '{}',
+    UNIQUE (speech_code, position)
+);
+
+CREATE TABLE IF NOT EXISTS answers (
+    question_id INT NOT NULL REFERENCES questions (id) ON DELETE CASCADE,
+    response_id INT NOT NULL REFERENCES responses (id) ON DELETE CASCADE,
+    answer TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (question_id, response_id)
+);
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS events (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(64) NOT NULL,
+    active BOOLEAN NOT NULL DEFAULT FALSE,
+    archived BOOLEAN NOT NULL DEFAULT FALSE,
+    UNIQUE (name)
+);
+
+-- At most one event can be active at a time
+CREATE UNIQUE INDEX IF NOT EXISTS events_single_active ON events (active) WHERE active;
+
+ALTER TABLE allowed_codes ADD COLUMN IF NOT EXISTS event_id INT REFERENCES events (id) ON DELETE SET NULL;
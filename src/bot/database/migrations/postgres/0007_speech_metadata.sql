@@ -0,0 +1,6 @@
+ALTER TABLE allowed_codes
+    ADD COLUMN IF NOT EXISTS title VARCHAR(256),
+    ADD COLUMN IF NOT EXISTS speaker VARCHAR(256),
+    ADD COLUMN IF NOT EXISTS room VARCHAR(64),
+    ADD COLUMN IF NOT EXISTS starts_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS ends_at TIMESTAMPTZ;
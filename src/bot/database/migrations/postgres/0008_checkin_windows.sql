@@ -0,0 +1,4 @@
+ALTER TABLE allowed_codes
+    ADD COLUMN IF NOT EXISTS window_before INT,
+    ADD COLUMN IF NOT EXISTS window_after INT,
+    ADD COLUMN IF NOT EXISTS checkin_override BOOLEAN;
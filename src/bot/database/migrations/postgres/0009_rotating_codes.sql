@@ -0,0 +1,3 @@
+ALTER TABLE allowed_codes
+    ADD COLUMN IF NOT EXISTS rotation_secret VARCHAR(64),
+    ADD COLUMN IF NOT EXISTS rotation_period INT;
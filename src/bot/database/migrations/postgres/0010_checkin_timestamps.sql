@@ -0,0 +1,8 @@
+-- Existing responses get no check-in time, new ones default to the insertion time
+DO $$ BEGIN
+    IF NOT EXISTS (SELECT 1 FROM information_schema.columns
+        WHERE table_name = 'responses' AND column_name = 'created_at') THEN
+        ALTER TABLE responses ADD COLUMN created_at TIMESTAMPTZ;
+        ALTER TABLE responses ALTER COLUMN created_at SET DEFAULT now();
+    END IF;
+END $$;
@@ -0,0 +1,4 @@
+-- Telegram ids no longer fit into INT (new accounts and group chats); widening keeps every row
+ALTER TABLE users ALTER COLUMN telegram_id TYPE BIGINT;
+ALTER TABLE responses ALTER COLUMN telegram_id TYPE BIGINT;
+ALTER TABLE admins ALTER COLUMN telegram_id TYPE BIGINT;
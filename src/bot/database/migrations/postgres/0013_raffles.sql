@@ -0,0 +1,25 @@
+-- Users left out of prize raffles
+CREATE TABLE IF NOT EXISTS raffle_bans (
+    telegram_id BIGINT PRIMARY KEY,
+    banned_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- Every draw with everything it was drawn from, so that it can be reproduced from its seed
+CREATE TABLE IF NOT EXISTS raffles (
+    id SERIAL PRIMARY KEY,
+    seed TEXT NOT NULL,
+    min_talks INT NOT NULL,
+    codes TEXT[] NOT NULL DEFAULT '{}',
+    candidates BIGINT[] NOT NULL,
+    drawn_by BIGINT NOT NULL,
+    drawn_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS raffle_winners (
+    raffle_id INT NOT NULL REFERENCES raffles (id) ON DELETE CASCADE,
+    position INT NOT NULL,
+    telegram_id BIGINT NOT NULL,
+    PRIMARY KEY (raffle_id, position)
+);
+
+CREATE INDEX IF NOT EXISTS raffle_winners_telegram_id ON raffle_winners (telegram_id);
@@ -0,0 +1,80 @@
+-- SQLite databases start from the schema the Postgres migrations have built up to
+CREATE TABLE IF NOT EXISTS events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name VARCHAR(64) NOT NULL,
+    active BOOLEAN NOT NULL DEFAULT FALSE,
+    archived BOOLEAN NOT NULL DEFAULT FALSE,
+    UNIQUE (name)
+);
+
+-- At most one event can be active at a time
+CREATE UNIQUE INDEX IF NOT EXISTS events_single_active ON events (active) WHERE active;
+
+CREATE TABLE IF NOT EXISTS allowed_codes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    code VARCHAR(32) NOT NULL,
+    feedback_open BOOLEAN NOT NULL DEFAULT TRUE,
+    event_id INTEGER REFERENCES events (id) ON DELETE SET NULL,
+    title VARCHAR(256),
+    speaker VARCHAR(256),
+    room VARCHAR(64),
+    starts_at DATETIME,
+    ends_at DATETIME,
+    window_before INTEGER,
+    window_after INTEGER,
+    checkin_override BOOLEAN,
+    rotation_secret VARCHAR(64),
+    rotation_period INTEGER,
+    UNIQUE (code)
+);
+
+CREATE TABLE IF NOT EXISTS users (
+    telegram_id INTEGER PRIMARY KEY,
+    first_name VARCHAR(64) NOT NULL,
+    last_name VARCHAR(64) NOT NULL,
+    username VARCHAR(32) NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS responses (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    speech_code VARCHAR(32) NOT NULL,
+    telegram_id INTEGER NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (telegram_id, speech_code)
+);
+
+CREATE TABLE IF NOT EXISTS admins (
+    telegram_id INTEGER PRIMARY KEY,
+    role VARCHAR(16) NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS ratings (
+    response_id INTEGER PRIMARY KEY REFERENCES responses (id) ON DELETE CASCADE,
+    rating INTEGER NOT NULL CHECK (rating BETWEEN 1 AND 5)
+);
+
+CREATE TABLE IF NOT EXISTS comments (
+    response_id INTEGER PRIMARY KEY REFERENCES responses (id) ON DELETE CASCADE,
+    text TEXT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME
+);
+
+-- Options are stored as a JSON array of strings
+CREATE TABLE IF NOT EXISTS questions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    speech_code VARCHAR(32) NOT NULL REFERENCES allowed_codes (code) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    kind VARCHAR(16) NOT NULL,
+    text TEXT NOT NULL,
+    options TEXT NOT NULL DEFAULT '[]',
+    UNIQUE (speech_code, position)
+);
+
+CREATE TABLE IF NOT EXISTS answers (
+    question_id INTEGER NOT NULL REFERENCES questions (id) ON DELETE CASCADE,
+    response_id INTEGER NOT NULL REFERENCES responses (id) ON DELETE CASCADE,
+    answer TEXT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (question_id, response_id)
+);
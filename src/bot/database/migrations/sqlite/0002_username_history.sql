@@ -0,0 +1,12 @@
+-- Every username a user has been seen with, so that old handles still resolve
+CREATE TABLE IF NOT EXISTS username_history (
+    telegram_id INTEGER NOT NULL REFERENCES users (telegram_id) ON DELETE CASCADE,
+    username VARCHAR(32) NOT NULL,
+    seen_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (telegram_id, username)
+);
+
+CREATE INDEX IF NOT EXISTS username_history_username ON username_history (LOWER(username));
+
+INSERT OR IGNORE INTO username_history (telegram_id, username)
+SELECT telegram_id, username FROM users WHERE username <> '';
@@ -0,0 +1,25 @@
+-- Users left out of prize raffles
+CREATE TABLE IF NOT EXISTS raffle_bans (
+    telegram_id INTEGER PRIMARY KEY,
+    banned_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Every draw with everything it was drawn from, so that it can be reproduced from its seed
+CREATE TABLE IF NOT EXISTS raffles (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    seed TEXT NOT NULL,
+    min_talks INTEGER NOT NULL,
+    codes TEXT NOT NULL DEFAULT '[]',
+    candidates TEXT NOT NULL,
+    drawn_by INTEGER NOT NULL,
+    drawn_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS raffle_winners (
+    raffle_id INTEGER NOT NULL REFERENCES raffles (id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    telegram_id INTEGER NOT NULL,
+    PRIMARY KEY (raffle_id, position)
+);
+
+CREATE INDEX IF NOT EXISTS raffle_winners_telegram_id ON raffle_winners (telegram_id);
@@ -0,0 +1,227 @@
+//! Row mapping shared by the SQL backends, which differ only in their queries
+
+use chrono::{DateTime, Utc};
+use sqlx::{ColumnIndex, Decode, FromRow, Row, Type};
+use std::collections::HashMap;
+
+use super::{Admin, CodeCheckIns, Comment, Event, FullResponse, Response, Role, Speech, SurveyAnswers, User};
+
+/// A type a backend can read from a column; spares spelling out both bounds for every type
+pub(super) trait Column<'r, DB: sqlx::Database>: Decode<'r, DB> + Type<DB> {}
+
+impl<'r, DB: sqlx::Database, T: Decode<'r, DB> + Type<DB>> Column<'r, DB> for T {}
+
+impl<'r, R: Row> FromRow<'r, R> for User
+where
+    &'r str: ColumnIndex<R>,
+    i64: Column<'r, R::Database>,
+    String: Column<'r, R::Database>,
+{
+    fn from_row(row: &'r R) -> sqlx::Result<Self> {
+        Ok(User {
+            telegram_id: row.try_get("telegram_id")?,
+            username: row.try_get("username")?,
+            first_name: row.try_get("first_name")?,
+            last_name: row.try_get("last_name")?,
+        })
+    }
+}
+
+impl<'r, R: Row> FromRow<'r, R> for Speech
+where
+    &'r str: ColumnIndex<R>,
+    i32: Column<'r, R::Database>,
+    bool: Column<'r, R::Database>,
+    String: Column<'r, R::Database>,
+    DateTime<Utc>: Column<'r, R::Database>,
+{
+    fn from_row(row: &'r R) -> sqlx::Result<Self> {
+        Ok(Speech {
+            code: row.try_get("code")?,
+            title: row.try_get("title")?,
+            speaker: row.try_get("speaker")?,
+            room: row.try_get("room")?,
+            starts_at: row.try_get("starts_at")?,
+            ends_at: row.try_get("ends_at")?,
+            window: match (row.try_get("window_before")?, row.try_get("window_after")?) {
+                (Some(before), Some(after)) => Some((before, after)),
+                _ => None,
+            },
+            checkin_override: row.try_get("checkin_override")?,
+            rotation: match (row.try_get("rotation_secret")?, row.try_get("rotation_period")?) {
+                (Some(secret), Some(period)) => Some((secret, period)),
+                _ => None,
+            },
+        })
+    }
+}
+
+/// A check-in with its rating and the title of its speech
+impl<'r, R: Row> FromRow<'r, R> for Response
+where
+    &'r str: ColumnIndex<R>,
+    i32: Column<'r, R::Database>,
+    i64: Column<'r, R::Database>,
+    String: Column<'r, R::Database>,
+    DateTime<Utc>: Column<'r, R::Database>,
+{
+    fn from_row(row: &'r R) -> sqlx::Result<Self> {
+        Ok(Response {
+            id: Some(row.try_get("id")?),
+            speech_code: row.try_get("speech_code")?,
+            telegram_id: row.try_get("telegram_id")?,
+            rating: row.try_get("rating")?,
+            speech_title: row.try_get("speech_title")?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
+
+impl<'r, R: Row> FromRow<'r, R> for FullResponse
+where
+    &'r str: ColumnIndex<R>,
+    i32: Column<'r, R::Database>,
+    i64: Column<'r, R::Database>,
+    String: Column<'r, R::Database>,
+    DateTime<Utc>: Column<'r, R::Database>,
+{
+    fn from_row(row: &'r R) -> sqlx::Result<Self> {
+        Ok(FullResponse {
+            id: Some(row.try_get("id")?),
+            speech_code: row.try_get("speech_code")?,
+            speech_title: row.try_get("speech_title")?,
+            created_at: row.try_get("created_at")?,
+            telegram_id: row.try_get("telegram_id")?,
+            username: row.try_get("username")?,
+            first_name: row.try_get("first_name")?,
+            last_name: row.try_get("last_name")?,
+            rating: row.try_get("rating")?,
+        })
+    }
+}
+
+impl<'r, R: Row> FromRow<'r, R> for Comment
+where
+    &'r str: ColumnIndex<R>,
+    i64: Column<'r, R::Database>,
+    String: Column<'r, R::Database>,
+    DateTime<Utc>: Column<'r, R::Database>,
+{
+    fn from_row(row: &'r R) -> sqlx::Result<Self> {
+        Ok(Comment {
+            speech_code: row.try_get("speech_code")?,
+            speech_title: row.try_get("speech_title")?,
+            checked_in_at: row.try_get("checked_in_at")?,
+            telegram_id: row.try_get("telegram_id")?,
+            username: row.try_get("username")?,
+            first_name: row.try_get("first_name")?,
+            last_name: row.try_get("last_name")?,
+            text: row.try_get("text")?,
+            created_at: row.try_get("created_at")?,
+            updated_at: row.try_get("updated_at")?,
+        })
+    }
+}
+
+impl<'r, R: Row> FromRow<'r, R> for Event
+where
+    &'r str: ColumnIndex<R>,
+    i32: Column<'r, R::Database>,
+    bool: Column<'r, R::Database>,
+    String: Column<'r, R::Database>,
+{
+    fn from_row(row: &'r R) -> sqlx::Result<Self> {
+        Ok(Event {
+            id: row.try_get("id")?,
+            name: row.try_get("name")?,
+            active: row.try_get("active")?,
+            archived: row.try_get("archived")?,
+        })
+    }
+}
+
+impl<'r, R: Row> FromRow<'r, R> for Admin
+where
+    &'r str: ColumnIndex<R>,
+    i64: Column<'r, R::Database>,
+    String: Column<'r, R::Database>,
+{
+    fn from_row(row: &'r R) -> sqlx::Result<Self> {
+        let role: String = row.try_get("role")?;
+        Ok(Admin {
+            telegram_id: row.try_get("telegram_id")?,
+            role: role.parse().unwrap_or(Role::Viewer),
+            username: row.try_get("username")?,
+        })
+    }
+}
+
+/// A code with its title and number of check-ins
+impl<'r, R: Row> FromRow<'r, R> for CodeCheckIns
+where
+    &'r str: ColumnIndex<R>,
+    i64: Column<'r, R::Database>,
+    String: Column<'r, R::Database>,
+{
+    fn from_row(row: &'r R) -> sqlx::Result<Self> {
+        Ok(CodeCheckIns {
+            speech_code: row.try_get("code")?,
+            speech_title: row.try_get("title")?,
+            check_ins: row.try_get("count")?,
+        })
+    }
+}
+
+/// A single survey answer along with the attendee who gave it
+pub(super) struct AnswerRow {
+    attendee: SurveyAnswers,
+    question_id: i32,
+    answer: String,
+}
+
+impl<'r, R: Row> FromRow<'r, R> for AnswerRow
+where
+    &'r str: ColumnIndex<R>,
+    i32: Column<'r, R::Database>,
+    i64: Column<'r, R::Database>,
+    String: Column<'r, R::Database>,
+    DateTime<Utc>: Column<'r, R::Database>,
+{
+    fn from_row(row: &'r R) -> sqlx::Result<Self> {
+        Ok(AnswerRow {
+            attendee: SurveyAnswers {
+                telegram_id: row.try_get("telegram_id")?,
+                checked_in_at: row.try_get("created_at")?,
+                username: row.try_get("username")?,
+                first_name: row.try_get("first_name")?,
+                last_name: row.try_get("last_name")?,
+                answers: HashMap::new(),
+            },
+            question_id: row.try_get("question_id")?,
+            answer: row.try_get("answer")?,
+        })
+    }
+}
+
+/// Groups answers ordered by attendee into one `SurveyAnswers` per attendee
+pub(super) fn group_answers(rows: Vec<AnswerRow>) -> Vec<SurveyAnswers> {
+    let mut survey_answers: Vec<SurveyAnswers> = Vec::new();
+    for row in rows {
+        if survey_answers.last().map(|a| a.telegram_id) != Some(row.attendee.telegram_id) {
+            survey_answers.push(row.attendee);
+        }
+        if let Some(attendee) = survey_answers.last_mut() {
+            attendee.answers.insert(row.question_id, row.answer);
+        }
+    }
+    survey_answers
+}
+
+/// Ratings by the number of stars, from `(rating, count)` rows
+pub(super) fn distribution(rows: Vec<(i32, i64)>) -> [i64; 5] {
+    let mut distribution = [0; 5];
+    for (rating, count) in rows {
+        distribution[(rating - 1) as usize] = count;
+    }
+    distribution
+}
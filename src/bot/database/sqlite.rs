@@ -0,0 +1,781 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::stream::{BoxStream, StreamExt};
+use futures::TryStreamExt;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions};
+use sqlx::types::Json;
+use sqlx::Row;  // import for get() function on sqlx queries
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::bot::error::{Error, Result};
+use super::{
+    rows, Admin, Comment, Event, ExportFilter, FullResponse, Migration, Question, QuestionKind, Raffle,
+    RatingSummary, Response, ResponseScope, Role, Speech, SpeechField, Stats, Storage, SurveyAnswers, User,
+};
+
+/// SQLite migrations are versioned separately from the Postgres ones
+const MIGRATIONS: &[Migration] = &[
+    migration!(1, "sqlite", "0001_initial"),
+    migration!(2, "sqlite", "0002_username_history"),
+    migration!(3, "sqlite", "0003_raffles"),
+];
+
+/// Check-ins with everything an export needs; parameters come from `ResponseScope::into_params`
+/// and then `ExportFilter`, with its lists as JSON arrays
+const FULL_RESPONSES: &str = "SELECT responses.id, responses.speech_code, responses.telegram_id, responses.created_at,
+        allowed_codes.title AS speech_title, ratings.rating,
+        COALESCE(users.username, '') AS username,
+        COALESCE(users.first_name, '') AS first_name,
+        COALESCE(users.last_name, '') AS last_name
+    FROM responses
+    LEFT JOIN allowed_codes ON allowed_codes.code = responses.speech_code
+    LEFT JOIN ratings ON ratings.response_id = responses.id
+    LEFT JOIN users ON users.telegram_id = responses.telegram_id
+    WHERE ($1 IS NULL OR responses.speech_code = $1)
+        AND ($2 IS NULL OR responses.telegram_id = $2)
+        AND (NOT $3 OR allowed_codes.code IS NOT NULL)
+        AND ($4 IS NULL OR allowed_codes.event_id = $4)
+        AND ($5 IS NULL OR responses.speech_code IN (SELECT value FROM json_each($5)))
+        AND ($6 IS NULL OR datetime(responses.created_at) >= datetime($6))
+        AND ($7 IS NULL OR datetime(responses.created_at) < datetime($7))
+        AND ($8 IS NULL OR responses.telegram_id IN (SELECT value FROM json_each($8)))
+    ORDER BY allowed_codes.id IS NULL, allowed_codes.id, responses.id";
+
+/// Current and past usernames; a handle that changed hands resolves to whoever was seen with it last
+const USER_ID_BY_USERNAME: &str = "SELECT telegram_id FROM username_history
+    WHERE LOWER(username) = LOWER($1)
+    ORDER BY seen_at DESC
+    LIMIT 1";
+
+pub struct SqliteStorage {
+    pool: sqlx::SqlitePool,
+}
+
+impl SqliteStorage {
+    pub async fn new(database_url: &str) -> Result<Self> {
+        // With a write-ahead log an open export stream does not block check-ins,
+        // and a writer waits for a lock instead of failing at once
+        let options = SqliteConnectOptions::from_str(database_url)?
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal)
+            .busy_timeout(Duration::from_secs(10));
+        let pool = SqlitePoolOptions::new()
+            .max_connections(5)
+            .connect_with(options)
+            .await?;
+
+        Ok(Self { pool })
+    }
+}
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    async fn pending_migrations(&self) -> Result<Vec<&'static Migration>> {
+        let mut applied: Vec<i32> = Vec::new();
+        let tracked = sqlx::query("SELECT EXISTS (
+            SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'schema_migrations'
+        ) AS tracked")
+            .fetch_one(&self.pool)
+            .await?;
+        if tracked.get("tracked") {
+            let mut rows = sqlx::query("SELECT version FROM schema_migrations")
+                .fetch(&self.pool);
+            while let Some(row) = rows.try_next().await? {
+                applied.push(row.get("version"));
+            }
+        }
+        Ok(MIGRATIONS.iter()
+            .filter(|m| !applied.contains(&m.version))
+            .collect())
+    }
+
+    async fn migrate(&self) -> Result<Vec<&'static Migration>> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INTEGER PRIMARY KEY,
+            name VARCHAR(128) NOT NULL,
+            applied_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )")
+            .execute(&mut tx)
+            .await?;
+        let mut applied: Vec<i32> = Vec::new();
+        {
+            let mut rows = sqlx::query("SELECT version FROM schema_migrations")
+                .fetch(&mut tx);
+            while let Some(row) = rows.try_next().await? {
+                applied.push(row.get("version"));
+            }
+        }
+
+        let mut pending: Vec<&'static Migration> = Vec::new();
+        for migration in MIGRATIONS.iter().filter(|m| !applied.contains(&m.version)) {
+            // A plain string runs as a simple query, which allows several statements
+            sqlx::Executor::execute(&mut tx, migration.sql).await?;
+            sqlx::query("INSERT INTO schema_migrations (version, name) VALUES ($1, $2)")
+                .bind(migration.version)
+                .bind(migration.name)
+                .execute(&mut tx)
+                .await?;
+            pending.push(migration);
+        }
+        tx.commit().await?;
+        Ok(pending)
+    }
+
+    async fn upsert_user(&self, user: &User) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("INSERT INTO users (telegram_id, username, first_name, last_name) VALUES ($1, $2, $3, $4)
+            ON CONFLICT (telegram_id) DO UPDATE
+            SET username = excluded.username, first_name = excluded.first_name, last_name = excluded.last_name")
+            .bind(user.telegram_id)
+            .bind(&user.username)
+            .bind(&user.first_name)
+            .bind(&user.last_name)
+            .execute(&mut tx)
+            .await?;
+        if !user.username.is_empty() {
+            sqlx::query("INSERT INTO username_history (telegram_id, username) VALUES ($1, $2)
+                ON CONFLICT (telegram_id, username) DO UPDATE SET seen_at = CURRENT_TIMESTAMP")
+                .bind(user.telegram_id)
+                .bind(&user.username)
+                .execute(&mut tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn get_users(&self) -> Result<Vec<i64>> {
+        let mut users: Vec<i64> = Vec::new();
+        let mut rows = sqlx::query("SELECT telegram_id FROM users")
+            .fetch(&self.pool);
+        while let Some(row) = rows.try_next().await? {
+            users.push(row.get("telegram_id"));
+        }
+        Ok(users)
+    }
+
+    async fn get_profiles(&self) -> Result<Vec<User>> {
+        let users = sqlx::query_as("SELECT * FROM users")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(users)
+    }
+
+    async fn add_code(&self, code: &str) -> Result<()> {
+        sqlx::query("INSERT INTO allowed_codes (code, event_id) VALUES ($1, (SELECT id FROM events WHERE active))
+            ON CONFLICT (code) DO NOTHING")
+            .bind(code)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn del_code(&self, code: &str) -> Result<()> {
+        sqlx::query("DELETE FROM allowed_codes WHERE code = $1")
+            .bind(code)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn flush_responses(&self) -> Result<()> {
+        sqlx::query("DELETE FROM responses")
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn flush_codes(&self) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM allowed_codes")
+            .execute(&mut tx)
+            .await?;
+        sqlx::query("DELETE FROM responses")
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn get_codes(&self, event: Option<i32>) -> Result<Vec<String>> {
+        let mut codes: Vec<String> = Vec::new();
+        let mut rows = sqlx::query("SELECT * FROM allowed_codes WHERE $1 IS NULL OR event_id = $1")
+            .bind(event)
+            .fetch(&self.pool);
+        while let Some(row) = rows.try_next().await? {
+            codes.push(row.get("code"));
+        }
+        Ok(codes)
+    }
+
+    async fn get_speech(&self, code: &str) -> Result<Option<Speech>> {
+        let speech = sqlx::query_as("SELECT * FROM allowed_codes WHERE code = $1")
+            .bind(code)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(speech)
+    }
+
+    async fn set_rotation(&self, code: &str, rotation: Option<(String, i32)>) -> Result<bool> {
+        let (secret, period) = rotation.unzip();
+        let result = sqlx::query("UPDATE allowed_codes SET rotation_secret = $2, rotation_period = $3 WHERE code = $1")
+            .bind(code)
+            .bind(secret)
+            .bind(period)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn set_checkin_window(&self, code: &str, window: Option<(i32, i32)>) -> Result<bool> {
+        let result = sqlx::query("UPDATE allowed_codes SET window_before = $2, window_after = $3 WHERE code = $1")
+            .bind(code)
+            .bind(window.map(|(before, _)| before))
+            .bind(window.map(|(_, after)| after))
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn set_checkin_override(&self, code: &str, open: Option<bool>) -> Result<bool> {
+        let result = sqlx::query("UPDATE allowed_codes SET checkin_override = $2 WHERE code = $1")
+            .bind(code)
+            .bind(open)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn set_speech_field(&self, code: &str, field: SpeechField, value: &str) -> Result<bool> {
+        let query = match field {
+            SpeechField::Title => "UPDATE allowed_codes SET title = NULLIF($2, '') WHERE code = $1",
+            SpeechField::Speaker => "UPDATE allowed_codes SET speaker = NULLIF($2, '') WHERE code = $1",
+            SpeechField::Room => "UPDATE allowed_codes SET room = NULLIF($2, '') WHERE code = $1",
+        };
+        let result = sqlx::query(query)
+            .bind(code)
+            .bind(value)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn set_speech_schedule(&self, code: &str, starts_at: Option<DateTime<Utc>>, ends_at: Option<DateTime<Utc>>) -> Result<bool> {
+        let result = sqlx::query("UPDATE allowed_codes SET starts_at = $2, ends_at = $3 WHERE code = $1")
+            .bind(code)
+            .bind(starts_at)
+            .bind(ends_at)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn is_code_allowed(&self, code: &str) -> Result<bool> {
+        let row = sqlx::query("SELECT allowed_codes.code FROM allowed_codes
+            LEFT JOIN events ON events.id = allowed_codes.event_id
+            WHERE allowed_codes.code = $1 AND events.archived IS NOT TRUE")
+            .bind(code)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.is_some())
+    }
+
+    async fn insert(&self, response: Response) -> Result<()> {
+        // A repeated check-in keeps the first one
+        sqlx::query("INSERT INTO responses (speech_code, telegram_id) VALUES ($1, $2)
+            ON CONFLICT (telegram_id, speech_code) DO NOTHING")
+            .bind(response.speech_code)
+            .bind(response.telegram_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    // pub async fn get_all_responses(&self) -> Result<Vec<Response>> {
+    //     let mut responses: Vec<Response> = Vec::new();
+    //     let mut rows = sqlx::query("SELECT * FROM responses")
+    //         .fetch(&self.pool);
+
+    //     while let Some(row) = rows.try_next().await? {
+    //         responses.push(Response {
+    //             id: row.get("id"),
+    //             speech_code: row.get("speech_code"),
+    //             telegram_id: row.get("telegram_id"),
+    //         });
+    //     }
+
+    //     Ok(responses)
+    // }
+
+    async fn get_users_by_code(&self, code: String) -> Result<Vec<i64>> {
+        let mut users: Vec<i64> = Vec::new();
+        let mut rows = sqlx::query("SELECT telegram_id FROM responses WHERE speech_code = $1")
+            .bind(code)
+            .fetch(&self.pool);
+
+        while let Some(row) = rows.try_next().await? {
+            users.push(row.get("telegram_id"));
+        }
+
+        Ok(users)
+    }
+
+    async fn get_users_by_event(&self, event: i32) -> Result<Vec<i64>> {
+        let mut users: Vec<i64> = Vec::new();
+        let mut rows = sqlx::query("SELECT DISTINCT responses.telegram_id FROM responses
+            JOIN allowed_codes ON allowed_codes.code = responses.speech_code
+            WHERE allowed_codes.event_id = $1")
+            .bind(event)
+            .fetch(&self.pool);
+
+        while let Some(row) = rows.try_next().await? {
+            users.push(row.get("telegram_id"));
+        }
+
+        Ok(users)
+    }
+
+    async fn get_by_user(&self, user_id: i64) -> Result<Vec<Response>> {
+        let responses = sqlx::query_as("SELECT responses.*, ratings.rating, allowed_codes.title AS speech_title FROM responses
+            LEFT JOIN ratings ON ratings.response_id = responses.id
+            LEFT JOIN allowed_codes ON allowed_codes.code = responses.speech_code
+            WHERE telegram_id = $1")
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(responses)
+    }
+
+    fn filtered_responses(&self, scope: ResponseScope, filter: ExportFilter) -> BoxStream<'_, Result<FullResponse>> {
+        let (code, telegram_id, known_codes, event) = scope.into_params();
+        let codes = (!filter.codes.is_empty()).then(|| serde_json::json!(filter.codes).to_string());
+        let users = (!filter.users.is_empty()).then(|| serde_json::json!(filter.users).to_string());
+        sqlx::query_as(FULL_RESPONSES)
+            .bind(code)
+            .bind(telegram_id)
+            .bind(known_codes)
+            .bind(event)
+            .bind(codes)
+            .bind(filter.from)
+            .bind(filter.until)
+            .bind(users)
+            .fetch(&self.pool)
+            .map_err(Error::from)
+            .boxed()
+    }
+
+    async fn is_feedback_open(&self, code: &str) -> Result<bool> {
+        let row = sqlx::query("SELECT feedback_open FROM allowed_codes WHERE code = $1")
+            .bind(code)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(|row| row.get("feedback_open")).unwrap_or(false))
+    }
+
+    async fn set_feedback_open(&self, code: &str, open: bool) -> Result<bool> {
+        let result = sqlx::query("UPDATE allowed_codes SET feedback_open = $2 WHERE code = $1")
+            .bind(code)
+            .bind(open)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn set_rating(&self, telegram_id: i64, code: &str, rating: i32) -> Result<bool> {
+        let result = sqlx::query("INSERT INTO ratings (response_id, rating)
+            SELECT id, $3 FROM responses WHERE telegram_id = $1 AND speech_code = $2
+            ON CONFLICT (response_id) DO UPDATE SET rating = EXCLUDED.rating")
+            .bind(telegram_id)
+            .bind(code)
+            .bind(rating)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_rating_summary(&self, code: &str) -> Result<RatingSummary> {
+        let ratings = sqlx::query_as("SELECT ratings.rating, COUNT(*) AS count FROM ratings
+            JOIN responses ON responses.id = ratings.response_id
+            WHERE responses.speech_code = $1
+            GROUP BY ratings.rating")
+            .bind(code)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(RatingSummary {
+            speech_code: code.to_string(),
+            speech_title: self.get_speech(code).await?.and_then(|speech| speech.title),
+            distribution: rows::distribution(ratings),
+        })
+    }
+
+    async fn get_stats(&self, event: Option<i32>, recent_since: DateTime<Utc>) -> Result<Stats> {
+        let codes = sqlx::query_as("SELECT allowed_codes.code, allowed_codes.title, COUNT(responses.id) AS count
+            FROM allowed_codes
+            LEFT JOIN responses ON responses.speech_code = allowed_codes.code
+            WHERE ($1 IS NULL OR allowed_codes.event_id = $1)
+            GROUP BY allowed_codes.id, allowed_codes.code, allowed_codes.title
+            ORDER BY allowed_codes.id")
+            .bind(event)
+            .fetch_all(&self.pool)
+            .await?;
+
+        let talks_per_attendee = sqlx::query_as("SELECT talks, COUNT(*) AS attendees FROM (
+                SELECT COUNT(*) AS talks FROM responses
+                JOIN allowed_codes ON allowed_codes.code = responses.speech_code
+                WHERE ($1 IS NULL OR allowed_codes.event_id = $1)
+                GROUP BY responses.telegram_id
+            ) AS per_attendee
+            GROUP BY talks
+            ORDER BY talks")
+            .bind(event)
+            .fetch_all(&self.pool)
+            .await?;
+
+        let (recent,) = sqlx::query_as("SELECT COUNT(*) FROM responses
+            JOIN allowed_codes ON allowed_codes.code = responses.speech_code
+            WHERE ($1 IS NULL OR allowed_codes.event_id = $1) AND datetime(responses.created_at) >= datetime($2)")
+            .bind(event)
+            .bind(recent_since)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(Stats { codes, talks_per_attendee, recent })
+    }
+
+    async fn get_timeline(&self, code: &str) -> Result<Vec<(DateTime<Utc>, i64)>> {
+        let mut timeline: Vec<(DateTime<Utc>, i64)> = Vec::new();
+        let mut rows = sqlx::query("SELECT strftime('%Y-%m-%d %H:%M:00', created_at) AS minute, COUNT(*) AS count
+            FROM responses
+            WHERE speech_code = $1 AND created_at IS NOT NULL
+            GROUP BY minute
+            ORDER BY minute")
+            .bind(code)
+            .fetch(&self.pool);
+
+        while let Some(row) = rows.try_next().await? {
+            timeline.push((row.get("minute"), row.get("count")));
+        }
+
+        Ok(timeline)
+    }
+
+    async fn has_response(&self, telegram_id: i64, code: &str) -> Result<bool> {
+        let row = sqlx::query("SELECT id FROM responses WHERE telegram_id = $1 AND speech_code = $2")
+            .bind(telegram_id)
+            .bind(code)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.is_some())
+    }
+
+    async fn set_comment(&self, telegram_id: i64, code: &str, text: &str) -> Result<bool> {
+        let result = sqlx::query("INSERT INTO comments (response_id, text)
+            SELECT id, $3 FROM responses WHERE telegram_id = $1 AND speech_code = $2
+            ON CONFLICT (response_id) DO UPDATE SET text = EXCLUDED.text, updated_at = CURRENT_TIMESTAMP")
+            .bind(telegram_id)
+            .bind(code)
+            .bind(text)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_comment(&self, telegram_id: i64, code: &str) -> Result<Option<String>> {
+        let row = sqlx::query("SELECT comments.text FROM comments
+            JOIN responses ON responses.id = comments.response_id
+            WHERE responses.telegram_id = $1 AND responses.speech_code = $2")
+            .bind(telegram_id)
+            .bind(code)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(|row| row.get("text")))
+    }
+
+    async fn delete_comment(&self, telegram_id: i64, code: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM comments WHERE response_id IN (
+            SELECT id FROM responses WHERE telegram_id = $1 AND speech_code = $2
+        )")
+            .bind(telegram_id)
+            .bind(code)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_comments_by_code(&self, code: &str) -> Result<Vec<Comment>> {
+        let comments = sqlx::query_as("SELECT responses.speech_code, allowed_codes.title AS speech_title,
+            responses.created_at AS checked_in_at, responses.telegram_id,
+            COALESCE(users.username, '') AS username,
+            COALESCE(users.first_name, '') AS first_name,
+            COALESCE(users.last_name, '') AS last_name,
+            comments.text, comments.created_at, comments.updated_at
+            FROM comments
+            JOIN responses ON responses.id = comments.response_id
+            LEFT JOIN users ON users.telegram_id = responses.telegram_id
+            LEFT JOIN allowed_codes ON allowed_codes.code = responses.speech_code
+            WHERE responses.speech_code = $1
+            ORDER BY comments.created_at")
+            .bind(code)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(comments)
+    }
+
+    async fn add_question(&self, code: &str, kind: QuestionKind, text: &str, options: &[String]) -> Result<i32> {
+        let mut tx = self.pool.begin().await?;
+        let row = sqlx::query("INSERT INTO questions (speech_code, position, kind, text, options)
+            SELECT $1, COALESCE(MAX(position), 0) + 1, $2, $3, $4 FROM questions WHERE speech_code = $1
+            RETURNING position")
+            .bind(code)
+            .bind(kind.as_str())
+            .bind(text)
+            .bind(Json(options))
+            .fetch_one(&mut tx)
+            .await?;
+        tx.commit().await?;
+        Ok(row.get("position"))
+    }
+
+    async fn del_question(&self, code: &str, position: i32) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM questions WHERE speech_code = $1 AND position = $2")
+            .bind(code)
+            .bind(position)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_questions(&self, code: &str) -> Result<Vec<Question>> {
+        let mut questions: Vec<Question> = Vec::new();
+        let mut rows = sqlx::query("SELECT * FROM questions WHERE speech_code = $1 ORDER BY position")
+            .bind(code)
+            .fetch(&self.pool);
+
+        while let Some(row) = rows.try_next().await? {
+            let kind: String = row.get("kind");
+            questions.push(Question {
+                id: row.get("id"),
+                position: row.get("position"),
+                kind: kind.parse().unwrap_or(QuestionKind::Text),
+                text: row.get("text"),
+                options: row.get::<Json<Vec<String>>, _>("options").0,
+            });
+        }
+
+        Ok(questions)
+    }
+
+    async fn set_answer(&self, question_id: i32, telegram_id: i64, code: &str, answer: &str) -> Result<bool> {
+        let result = sqlx::query("INSERT INTO answers (question_id, response_id, answer)
+            SELECT $1, id, $4 FROM responses WHERE telegram_id = $2 AND speech_code = $3
+            ON CONFLICT (question_id, response_id) DO UPDATE SET answer = EXCLUDED.answer, created_at = CURRENT_TIMESTAMP")
+            .bind(question_id)
+            .bind(telegram_id)
+            .bind(code)
+            .bind(answer)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_survey_answers(&self, code: &str) -> Result<Vec<SurveyAnswers>> {
+        let answers = sqlx::query_as("SELECT responses.telegram_id, responses.created_at,
+            COALESCE(users.username, '') AS username,
+            COALESCE(users.first_name, '') AS first_name,
+            COALESCE(users.last_name, '') AS last_name,
+            answers.question_id, answers.answer
+            FROM answers
+            JOIN responses ON responses.id = answers.response_id
+            LEFT JOIN users ON users.telegram_id = responses.telegram_id
+            WHERE responses.speech_code = $1
+            ORDER BY responses.telegram_id")
+            .bind(code)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows::group_answers(answers))
+    }
+
+    async fn add_event(&self, name: &str) -> Result<bool> {
+        let result = sqlx::query("INSERT INTO events (name) VALUES ($1) ON CONFLICT (name) DO NOTHING")
+            .bind(name)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_events(&self) -> Result<Vec<Event>> {
+        let events = sqlx::query_as("SELECT * FROM events ORDER BY id")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(events)
+    }
+
+    async fn get_event(&self, name: &str) -> Result<Option<Event>> {
+        let event = sqlx::query_as("SELECT * FROM events WHERE name = $1")
+            .bind(name)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(event)
+    }
+
+    async fn set_active_event(&self, event: i32) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("UPDATE events SET active = FALSE WHERE active")
+            .execute(&mut tx)
+            .await?;
+        sqlx::query("UPDATE events SET active = TRUE, archived = FALSE WHERE id = $1")
+            .bind(event)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn archive_event(&self, event: i32) -> Result<()> {
+        sqlx::query("UPDATE events SET archived = TRUE, active = FALSE WHERE id = $1")
+            .bind(event)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn set_code_event(&self, code: &str, event: i32) -> Result<bool> {
+        let result = sqlx::query("UPDATE allowed_codes SET event_id = $2 WHERE code = $1")
+            .bind(code)
+            .bind(event)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_user_id_by_username(&self, username: &str) -> Result<Option<i64>> {
+        let row = sqlx::query(USER_ID_BY_USERNAME)
+            .bind(username)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(|row| row.get("telegram_id")))
+    }
+
+    async fn get_role(&self, telegram_id: i64) -> Result<Option<Role>> {
+        let row = sqlx::query("SELECT role FROM admins WHERE telegram_id = $1")
+            .bind(telegram_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.and_then(|row| row.get::<String, _>("role").parse().ok()))
+    }
+
+    async fn set_role(&self, telegram_id: i64, role: Role) -> Result<()> {
+        sqlx::query("INSERT INTO admins (telegram_id, role) VALUES ($1, $2)
+            ON CONFLICT (telegram_id) DO UPDATE SET role = EXCLUDED.role")
+            .bind(telegram_id)
+            .bind(role.as_str())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn revoke_admin(&self, telegram_id: i64) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM admins WHERE telegram_id = $1")
+            .bind(telegram_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn count_owners(&self) -> Result<i64> {
+        let row = sqlx::query("SELECT COUNT(*) AS count FROM admins WHERE role = $1")
+            .bind(Role::Owner.as_str())
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(row.get("count"))
+    }
+
+    async fn get_admins(&self) -> Result<Vec<Admin>> {
+        let admins = sqlx::query_as("SELECT admins.telegram_id, admins.role, users.username
+            FROM admins LEFT JOIN users ON users.telegram_id = admins.telegram_id
+            ORDER BY admins.telegram_id")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(admins)
+    }
+
+    async fn flush_responses_with_unknown_codes(&self) -> Result<()> {
+        // Deleting while iterating over the responses would wait for the reading connection's lock
+        sqlx::query("DELETE FROM responses WHERE speech_code NOT IN (SELECT code FROM allowed_codes)")
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+    // pub async fn get_all(&self) -> Result<Vec<Response>> {
+    //     let mut responses: Vec<Response> = Vec::new();
+    //     let mut rows = sqlx::query("SELECT * FROM responses")
+    //         .fetch(&self.pool);
+
+    //     while let Some(row) = rows.try_next().await {
+    //     }
+
+    //     Ok(vec![])
+    // }
+
+    async fn get_raffle_candidates(&self, min_talks: i32, codes: &[String]) -> Result<Vec<i64>> {
+        let mut candidates: Vec<i64> = Vec::new();
+        let mut rows = sqlx::query("SELECT responses.telegram_id FROM responses
+            JOIN allowed_codes ON allowed_codes.code = responses.speech_code
+            WHERE ($1 IS NULL OR responses.speech_code IN (SELECT value FROM json_each($1)))
+                AND responses.telegram_id NOT IN (SELECT telegram_id FROM raffle_bans)
+                AND responses.telegram_id NOT IN (SELECT telegram_id FROM raffle_winners)
+            GROUP BY responses.telegram_id
+            HAVING COUNT(DISTINCT responses.speech_code) >= $2
+            ORDER BY responses.telegram_id")
+            .bind((!codes.is_empty()).then_some(Json(codes)))
+            .bind(min_talks)
+            .fetch(&self.pool);
+        while let Some(row) = rows.try_next().await? {
+            candidates.push(row.get("telegram_id"));
+        }
+        Ok(candidates)
+    }
+
+    async fn add_raffle(&self, raffle: &Raffle) -> Result<i32> {
+        let mut tx = self.pool.begin().await?;
+        let row = sqlx::query("INSERT INTO raffles (seed, min_talks, codes, candidates, drawn_by)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id")
+            .bind(&raffle.seed)
+            .bind(raffle.min_talks)
+            .bind(Json(&raffle.codes))
+            .bind(Json(&raffle.candidates))
+            .bind(raffle.drawn_by)
+            .fetch_one(&mut tx)
+            .await?;
+        let id: i32 = row.get("id");
+        for (position, telegram_id) in raffle.winners.iter().enumerate() {
+            sqlx::query("INSERT INTO raffle_winners (raffle_id, position, telegram_id) VALUES ($1, $2, $3)")
+                .bind(id)
+                .bind(position as i32 + 1)
+                .bind(telegram_id)
+                .execute(&mut tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    async fn ban_from_raffles(&self, telegram_id: i64) -> Result<bool> {
+        let result = sqlx::query("INSERT INTO raffle_bans (telegram_id) VALUES ($1) ON CONFLICT DO NOTHING")
+            .bind(telegram_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn unban_from_raffles(&self, telegram_id: i64) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM raffle_bans WHERE telegram_id = $1")
+            .bind(telegram_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
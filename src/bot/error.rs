@@ -0,0 +1,86 @@
+use std::fmt;
+
+/// Everything that can go wrong while handling an update
+#[derive(Debug)]
+pub enum Error {
+    /// Query or connection failure of the storage backend
+    Database(sqlx::Error),
+    /// Failed request to the Telegram Bot API
+    Telegram(teloxide::RequestError),
+    /// Missing or invalid environment variable
+    Config(String),
+    /// Failed to build an export document
+    Export(String),
+    /// Invalid arguments or message; the text is a reply for the user
+    Input(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn input(message: impl Into<String>) -> Self {
+        Error::Input(message.into())
+    }
+
+    /// Input errors are the user's mistakes, everything else is worth logging
+    pub fn is_unexpected(&self) -> bool {
+        !matches!(self, Error::Input(_))
+    }
+
+    /// Localized reply for the user whose update failed
+    pub fn user_message(&self) -> String {
+        match self {
+            Error::Database(_) => "Не удалось обратиться к базе данных, попробуйте ещё раз через минуту".to_string(),
+            Error::Telegram(_) => "Telegram не ответил на запрос, попробуйте ещё раз через минуту".to_string(),
+            Error::Config(_) => "Бот настроен неправильно, сообщите об этом организаторам".to_string(),
+            Error::Export(_) => "Не удалось сформировать файл, сообщите об этом организаторам".to_string(),
+            Error::Input(message) => message.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Database(err) => write!(f, "database error: {}", err),
+            Error::Telegram(err) => write!(f, "Telegram error: {}", err),
+            Error::Config(message) => write!(f, "configuration error: {}", message),
+            Error::Export(message) => write!(f, "export error: {}", message),
+            Error::Input(message) => write!(f, "invalid input: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Database(err) => Some(err),
+            Error::Telegram(err) => Some(err),
+            Error::Config(_) | Error::Export(_) | Error::Input(_) => None,
+        }
+    }
+}
+
+impl From<sqlx::Error> for Error {
+    fn from(err: sqlx::Error) -> Self {
+        Error::Database(err)
+    }
+}
+
+impl From<teloxide::RequestError> for Error {
+    fn from(err: teloxide::RequestError) -> Self {
+        Error::Telegram(err)
+    }
+}
+
+impl From<rust_xlsxwriter::XlsxError> for Error {
+    fn from(err: rust_xlsxwriter::XlsxError) -> Self {
+        Error::Export(err.to_string())
+    }
+}
+
+impl From<csv::Error> for Error {
+    fn from(err: csv::Error) -> Self {
+        Error::Export(err.to_string())
+    }
+}
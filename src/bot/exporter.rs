@@ -0,0 +1,376 @@
+//! JSON and NDJSON exports for scripts. The records below are the documented schema
+//! (see "Export formats" in the README): fields are only ever added, anything
+//! incompatible bumps `SCHEMA_VERSION`.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashSet;
+
+use crate::bot::database::{
+    Comment, FullResponse, Question, RatingSummary, Speech, SurveyAnswers, User, UsernameResult,
+};
+use crate::bot::error::{Error, Result};
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// A Telegram user as last seen by the bot
+#[derive(Serialize)]
+pub struct UserRecord {
+    pub telegram_id: i64,
+    /// Empty if the user has no username
+    pub username: String,
+    pub first_name: String,
+    pub last_name: String,
+}
+
+/// A speech code and the talk it stands for
+#[derive(Serialize)]
+pub struct CodeRecord {
+    pub code: String,
+    pub title: Option<String>,
+    pub speaker: Option<String>,
+    pub room: Option<String>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+}
+
+/// A check-in of a user for a code
+#[derive(Serialize)]
+pub struct ResponseRecord {
+    pub id: Option<i32>,
+    pub code: String,
+    pub telegram_id: i64,
+    /// Unknown for check-ins recorded before it was tracked
+    pub checked_in_at: Option<DateTime<Utc>>,
+    /// Star rating from 1 to 5
+    pub rating: Option<i32>,
+}
+
+/// All check-ins of a single user
+#[derive(Serialize)]
+pub struct AttendeeRecord {
+    pub telegram_id: i64,
+    /// Username, or the display name and id for users without one
+    pub label: String,
+    pub codes: Vec<String>,
+    pub first_check_in: Option<DateTime<Utc>>,
+    pub last_check_in: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct CommentRecord {
+    pub code: String,
+    pub telegram_id: i64,
+    pub text: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct QuestionRecord {
+    pub id: i32,
+    pub code: String,
+    pub position: i32,
+    /// `choice`, `scale` or `text`
+    pub kind: &'static str,
+    pub text: String,
+    /// Choices, or the bounds of a scale
+    pub options: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct AnswerRecord {
+    pub question_id: i32,
+    pub code: String,
+    pub telegram_id: i64,
+    pub answer: String,
+}
+
+/// Check-ins within a minute, for minutes with at least one
+#[derive(Serialize)]
+pub struct TimelineRecord {
+    pub code: String,
+    pub minute: DateTime<Utc>,
+    pub check_ins: i64,
+    /// Check-ins up to and including this minute
+    pub total: i64,
+}
+
+#[derive(Serialize)]
+pub struct RatingRecord {
+    pub code: String,
+    pub title: Option<String>,
+    pub count: i64,
+    pub average: Option<f64>,
+    /// Number of ratings for each star, from 1 to 5
+    pub distribution: [i64; 5],
+}
+
+/// A single NDJSON line, tagged with its `type`
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Record {
+    User(UserRecord),
+    Code(CodeRecord),
+    Response(ResponseRecord),
+    Attendee(AttendeeRecord),
+    Comment(CommentRecord),
+    Question(QuestionRecord),
+    Answer(AnswerRecord),
+    Timeline(TimelineRecord),
+    Rating(RatingRecord),
+}
+
+/// The JSON document: every kind of record in its own array, empty if the export has none
+#[derive(Serialize)]
+struct Document {
+    schema_version: u32,
+    export: String,
+    generated_at: DateTime<Utc>,
+    users: Vec<UserRecord>,
+    codes: Vec<CodeRecord>,
+    responses: Vec<ResponseRecord>,
+    attendees: Vec<AttendeeRecord>,
+    comments: Vec<CommentRecord>,
+    questions: Vec<QuestionRecord>,
+    answers: Vec<AnswerRecord>,
+    timeline: Vec<TimelineRecord>,
+    ratings: Vec<RatingRecord>,
+}
+
+/// The first NDJSON line
+#[derive(Serialize)]
+#[serde(tag = "type", rename = "header")]
+struct Header<'a> {
+    schema_version: u32,
+    export: &'a str,
+    generated_at: DateTime<Utc>,
+}
+
+enum Output {
+    Json(Box<Document>),
+    Ndjson(Vec<u8>),
+}
+
+/// Collects records into a JSON document, or writes them out as NDJSON lines as they come
+pub struct Exporter {
+    output: Output,
+    /// Users already written, so that response exports list each once
+    users: HashSet<i64>,
+}
+
+impl Exporter {
+    pub fn new(export: &str, ndjson: bool) -> Result<Self> {
+        let generated_at = Utc::now();
+        let output = if ndjson {
+            let header = Header {
+                schema_version: SCHEMA_VERSION,
+                export,
+                generated_at,
+            };
+            let mut buffer = serde_json::to_vec(&header).map_err(json_error)?;
+            buffer.push(b'\n');
+            Output::Ndjson(buffer)
+        } else {
+            Output::Json(Box::new(Document {
+                schema_version: SCHEMA_VERSION,
+                export: export.to_string(),
+                generated_at,
+                users: Vec::new(),
+                codes: Vec::new(),
+                responses: Vec::new(),
+                attendees: Vec::new(),
+                comments: Vec::new(),
+                questions: Vec::new(),
+                answers: Vec::new(),
+                timeline: Vec::new(),
+                ratings: Vec::new(),
+            }))
+        };
+        Ok(Self {
+            output,
+            users: HashSet::new(),
+        })
+    }
+
+    pub fn push(&mut self, record: Record) -> Result<()> {
+        match &mut self.output {
+            Output::Ndjson(buffer) => {
+                serde_json::to_writer(&mut *buffer, &record).map_err(json_error)?;
+                buffer.push(b'\n');
+            }
+            Output::Json(document) => match record {
+                Record::User(user) => document.users.push(user),
+                Record::Code(code) => document.codes.push(code),
+                Record::Response(response) => document.responses.push(response),
+                Record::Attendee(attendee) => document.attendees.push(attendee),
+                Record::Comment(comment) => document.comments.push(comment),
+                Record::Question(question) => document.questions.push(question),
+                Record::Answer(answer) => document.answers.push(answer),
+                Record::Timeline(bin) => document.timeline.push(bin),
+                Record::Rating(rating) => document.ratings.push(rating),
+            },
+        }
+        Ok(())
+    }
+
+    /// Pushes a user unless they have been pushed already
+    pub fn push_user(&mut self, user: UserRecord) -> Result<()> {
+        if self.users.insert(user.telegram_id) {
+            self.push(Record::User(user))?;
+        }
+        Ok(())
+    }
+
+    /// Pushes a check-in, preceded by its user if not pushed yet
+    pub fn push_response(&mut self, response: FullResponse) -> Result<()> {
+        self.push_user(UserRecord {
+            telegram_id: response.telegram_id,
+            username: response.username,
+            first_name: response.first_name,
+            last_name: response.last_name,
+        })?;
+        self.push(Record::Response(ResponseRecord {
+            id: response.id,
+            code: response.speech_code,
+            telegram_id: response.telegram_id,
+            checked_in_at: response.created_at,
+            rating: response.rating,
+        }))
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self.output {
+            Output::Json(_) => "json",
+            Output::Ndjson(_) => "ndjson",
+        }
+    }
+
+    pub fn finish(self) -> Result<Vec<u8>> {
+        match self.output {
+            Output::Ndjson(buffer) => Ok(buffer),
+            Output::Json(document) => serde_json::to_vec_pretty(&document).map_err(json_error),
+        }
+    }
+}
+
+fn json_error(err: serde_json::Error) -> Error {
+    Error::Export(err.to_string())
+}
+
+impl From<&User> for UserRecord {
+    fn from(user: &User) -> Self {
+        Self {
+            telegram_id: user.telegram_id,
+            username: user.username.clone(),
+            first_name: user.first_name.clone(),
+            last_name: user.last_name.clone(),
+        }
+    }
+}
+
+impl From<Speech> for CodeRecord {
+    fn from(speech: Speech) -> Self {
+        Self {
+            code: speech.code,
+            title: speech.title,
+            speaker: speech.speaker,
+            room: speech.room,
+            starts_at: speech.starts_at,
+            ends_at: speech.ends_at,
+        }
+    }
+}
+
+impl From<&UsernameResult> for AttendeeRecord {
+    fn from(result: &UsernameResult) -> Self {
+        let check_ins = result.responses.iter().filter_map(|response| response.created_at);
+        Self {
+            telegram_id: result.user.telegram_id,
+            label: result.user.label(),
+            codes: result.responses.iter().map(|response| response.speech_code.clone()).collect(),
+            first_check_in: check_ins.clone().min(),
+            last_check_in: check_ins.max(),
+        }
+    }
+}
+
+impl From<RatingSummary> for RatingRecord {
+    fn from(summary: RatingSummary) -> Self {
+        Self {
+            count: summary.count(),
+            average: summary.average(),
+            code: summary.speech_code,
+            title: summary.speech_title,
+            distribution: summary.distribution,
+        }
+    }
+}
+
+/// Comments with their authors
+pub fn push_comments(exporter: &mut Exporter, comments: Vec<Comment>) -> Result<()> {
+    for comment in comments {
+        exporter.push_user(UserRecord {
+            telegram_id: comment.telegram_id,
+            username: comment.username,
+            first_name: comment.first_name,
+            last_name: comment.last_name,
+        })?;
+        exporter.push(Record::Comment(CommentRecord {
+            code: comment.speech_code,
+            telegram_id: comment.telegram_id,
+            text: comment.text,
+            created_at: comment.created_at,
+            updated_at: comment.updated_at,
+        }))?;
+    }
+    Ok(())
+}
+
+/// Questions of a survey, then the respondents and their answers
+pub fn push_survey(exporter: &mut Exporter, code: &str, questions: Vec<Question>, answers: Vec<SurveyAnswers>) -> Result<()> {
+    for question in &questions {
+        exporter.push(Record::Question(QuestionRecord {
+            id: question.id,
+            code: code.to_string(),
+            position: question.position,
+            kind: question.kind.as_str(),
+            text: question.text.clone(),
+            options: question.options.clone(),
+        }))?;
+    }
+    for attendee in answers {
+        exporter.push_user(UserRecord {
+            telegram_id: attendee.telegram_id,
+            username: attendee.username,
+            first_name: attendee.first_name,
+            last_name: attendee.last_name,
+        })?;
+        // In question order, for stable output
+        for question in &questions {
+            if let Some(answer) = attendee.answers.get(&question.id) {
+                exporter.push(Record::Answer(AnswerRecord {
+                    question_id: question.id,
+                    code: code.to_string(),
+                    telegram_id: attendee.telegram_id,
+                    answer: answer.clone(),
+                }))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn push_timeline(exporter: &mut Exporter, code: &str, timeline: Vec<(DateTime<Utc>, i64)>) -> Result<()> {
+    let mut total: i64 = 0;
+    for (minute, check_ins) in timeline {
+        total += check_ins;
+        exporter.push(Record::Timeline(TimelineRecord {
+            code: code.to_string(),
+            minute,
+            check_ins,
+            total,
+        }))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,96 @@
+use ab_glyph::FontRef;
+use image::{GrayImage, ImageFormat, Luma};
+use qrcode::QrCode;
+use std::io::{Cursor, Write};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use crate::bot::error::Result;
+use crate::bot::text::{self, export_error};
+
+/// Size of the QR code itself, without the label
+const QR_SIZE: u32 = 600;
+const LABEL_FONT_SIZE: f32 = 32.0;
+const LABEL_LINE_HEIGHT: u32 = 40;
+const LABEL_PADDING: u32 = 24;
+
+/// Link that opens the bot and sends `/start <code>`
+pub fn deep_link(bot_username: &str, code: &str) -> String {
+    format!("https://t.me/{}?start={}", bot_username, code)
+}
+
+/// Renders the QR code of `link` as a PNG, with `label` lines printed under it
+pub fn create_qr_png(link: &str, label: &[String]) -> Result<Vec<u8>> {
+    let qr = QrCode::new(link.as_bytes())
+        .map_err(export_error)?
+        .render::<Luma<u8>>()
+        .min_dimensions(QR_SIZE, QR_SIZE)
+        .build();
+
+    let font = text::regular()?;
+    let width = qr.width();
+    let lines = label.iter()
+        .flat_map(|text| wrap_text(&font, text, (width - 2 * LABEL_PADDING) as f32))
+        .collect::<Vec<String>>();
+    let label_height = if lines.is_empty() {
+        0
+    } else {
+        lines.len() as u32 * LABEL_LINE_HEIGHT + LABEL_PADDING
+    };
+
+    let mut image = GrayImage::from_pixel(width, qr.height() + label_height, Luma([255]));
+    image::imageops::replace(&mut image, &qr, 0, 0);
+    for (i, line) in lines.iter().enumerate() {
+        let line_width = text_width(&font, line);
+        let x = ((width as f32 - line_width) / 2.0).max(0.0);
+        let y = (qr.height() + i as u32 * LABEL_LINE_HEIGHT) as f32;
+        text::draw(&font, LABEL_FONT_SIZE, line, x, y, |px, py, coverage| {
+            if let Some(pixel) = image.get_pixel_mut_checked(px, py) {
+                pixel.0[0] = pixel.0[0].min((255.0 * (1.0 - coverage)) as u8);
+            }
+        });
+    }
+
+    let mut png = Cursor::new(Vec::new());
+    image.write_to(&mut png, ImageFormat::Png).map_err(export_error)?;
+    Ok(png.into_inner())
+}
+
+/// Packs `(file name, contents)` pairs into a ZIP archive
+pub fn create_zip(files: Vec<(String, Vec<u8>)>) -> Result<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    // PNGs are already compressed
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    for (name, contents) in files {
+        zip.start_file(name, options).map_err(export_error)?;
+        zip.write_all(&contents).map_err(export_error)?;
+    }
+
+    Ok(zip.finish().map_err(export_error)?.into_inner())
+}
+
+fn text_width(font: &FontRef, text: &str) -> f32 {
+    text::width(font, LABEL_FONT_SIZE, text)
+}
+
+/// Splits text into lines that fit into `max_width` pixels
+fn wrap_text(font: &FontRef, text: &str, max_width: f32) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let candidate = if line.is_empty() {
+            word.to_string()
+        } else {
+            format!("{} {}", line, word)
+        };
+        if text_width(font, &candidate) > max_width && !line.is_empty() {
+            lines.push(line);
+            line = word.to_string();
+        } else {
+            line = candidate;
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
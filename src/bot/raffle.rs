@@ -0,0 +1,56 @@
+use rand::{distributions::Alphanumeric, Rng};
+use sha2::{Digest, Sha256};
+
+/// Random seed for a draw that has none announced in advance
+pub fn new_seed() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(16)
+        .map(char::from)
+        .collect()
+}
+
+/// Picks up to `winners` of the candidates, which are expected sorted by id. Round `i`, from 0,
+/// takes the first 8 bytes of SHA-256 of `<seed>:<i>` as a big-endian number and removes the
+/// candidate at that number modulo the candidates left, so anyone can repeat the draw.
+pub fn draw(seed: &str, candidates: &[i64], winners: usize) -> Vec<i64> {
+    let mut left = candidates.to_vec();
+    let mut drawn: Vec<i64> = Vec::new();
+    for round in 0..winners.min(candidates.len()) {
+        let digest = Sha256::digest(format!("{}:{}", seed, round).as_bytes());
+        let number = u64::from_be_bytes(digest[..8].try_into().unwrap());
+        drawn.push(left.remove((number % left.len() as u64) as usize));
+    }
+    drawn
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draw_is_repeatable() {
+        let candidates = (1..=50).collect::<Vec<i64>>();
+        assert_eq!(draw("seed", &candidates, 5), draw("seed", &candidates, 5));
+        assert_ne!(draw("seed", &candidates, 5), draw("other seed", &candidates, 5));
+    }
+
+    #[test]
+    fn draw_follows_the_documented_algorithm() {
+        // Worked out independently of this code, from the steps in the README
+        let candidates = [10, 20, 30, 40, 50];
+        assert_eq!(draw("seed", &candidates, 3), [40, 20, 30]);
+        // Later rounds do not change earlier winners
+        assert_eq!(draw("seed", &candidates, 1), [40]);
+    }
+
+    #[test]
+    fn draw_picks_each_candidate_at_most_once() {
+        let candidates = [3, 1, 2];
+        let mut winners = draw("seed", &candidates, 10);
+        winners.sort();
+        assert_eq!(winners, [1, 2, 3]);
+        assert!(draw("seed", &[], 3).is_empty());
+        assert!(draw("seed", &candidates, 0).is_empty());
+    }
+}
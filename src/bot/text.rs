@@ -0,0 +1,49 @@
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
+
+use crate::bot::error::{Error, Result};
+
+/// DejaVu Sans, which covers Cyrillic
+pub fn regular() -> Result<FontRef<'static>> {
+    FontRef::try_from_slice(dejavu::sans::regular()).map_err(export_error)
+}
+
+pub fn bold() -> Result<FontRef<'static>> {
+    FontRef::try_from_slice(dejavu::sans::bold()).map_err(export_error)
+}
+
+/// Width of a single line of text in pixels
+pub fn width(font: &FontRef, size: f32, text: &str) -> f32 {
+    let font = font.as_scaled(PxScale::from(size));
+    text.chars()
+        .map(|c| font.h_advance(font.glyph_id(c)))
+        .sum()
+}
+
+/// Lays out text with its top-left corner at `(x, y)` and calls `plot` with every pixel
+/// it covers and the coverage from 0 to 1; pixels left or above the image are skipped
+pub fn draw(font: &FontRef, size: f32, text: &str, x: f32, y: f32, mut plot: impl FnMut(u32, u32, f32)) {
+    let scale = PxScale::from(size);
+    let scaled = font.as_scaled(scale);
+    let mut caret = x;
+    for c in text.chars() {
+        let id = scaled.glyph_id(c);
+        let glyph = id.with_scale_and_position(scale, point(caret, y + scaled.ascent()));
+        caret += scaled.h_advance(id);
+        let Some(outlined) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outlined.px_bounds();
+        outlined.draw(|gx, gy, coverage| {
+            let px = bounds.min.x as i32 + gx as i32;
+            let py = bounds.min.y as i32 + gy as i32;
+            if px >= 0 && py >= 0 {
+                plot(px as u32, py as u32, coverage.clamp(0.0, 1.0));
+            }
+        });
+    }
+}
+
+/// Failures of rendering an image; the images are sent as files, like exports
+pub fn export_error(err: impl ToString) -> Error {
+    Error::Export(err.to_string())
+}
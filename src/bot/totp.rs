@@ -0,0 +1,85 @@
+use hmac::{Hmac, Mac};
+use rand::{distributions::Alphanumeric, Rng};
+use sha2::Sha256;
+
+/// Number of digits in a rotating suffix
+const DIGITS: u32 = 6;
+
+/// Random secret for a rotating code
+pub fn new_secret() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect()
+}
+
+/// Suffix valid during the `counter`-th period (RFC 4226 dynamic truncation over HMAC-SHA256)
+fn suffix(secret: &str, counter: i64) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(&counter.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let value = u32::from_be_bytes([hash[offset] & 0x7f, hash[offset + 1], hash[offset + 2], hash[offset + 3]]);
+    format!("{:0width$}", value % 10u32.pow(DIGITS), width = DIGITS as usize)
+}
+
+/// Check-in payload `<code>-<suffix>` for the period containing `timestamp`
+pub fn payload(code: &str, secret: &str, period: i32, timestamp: i64) -> String {
+    format!("{}-{}", code, suffix(secret, timestamp / period as i64))
+}
+
+/// Accepts the suffix of the current period and of the previous one
+pub fn verify(secret: &str, period: i32, candidate: &str, timestamp: i64) -> bool {
+    let counter = timestamp / period as i64;
+    [counter, counter - 1].iter().any(|c| suffix(secret, *c) == candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "0123456789abcdef0123456789abcdef";
+    const PERIOD: i32 = 30;
+
+    fn suffix_at(timestamp: i64) -> String {
+        payload("A1", SECRET, PERIOD, timestamp).split_off("A1-".len())
+    }
+
+    #[test]
+    fn payload_has_a_six_digit_suffix() {
+        let payload = payload("A1", SECRET, PERIOD, 1_700_000_000);
+        let (code, suffix) = payload.split_once('-').unwrap();
+        assert_eq!(code, "A1");
+        assert_eq!(suffix.len(), 6);
+        assert!(suffix.chars().all(|c| c.is_ascii_digit()));
+    }
+
+    #[test]
+    fn suffix_holds_for_a_period() {
+        let start = 1_700_000_010;
+        assert_eq!(start % PERIOD as i64, 0);
+        assert_eq!(suffix_at(start), suffix_at(start + PERIOD as i64 - 1));
+        assert_ne!(suffix_at(start), suffix_at(start + PERIOD as i64));
+    }
+
+    #[test]
+    fn verify_accepts_current_and_previous_period() {
+        let start = 1_700_000_010;
+        let suffix = suffix_at(start);
+        assert!(verify(SECRET, PERIOD, &suffix, start));
+        assert!(verify(SECRET, PERIOD, &suffix, start + PERIOD as i64 - 1));
+        // The last second of the next period still accepts it
+        assert!(verify(SECRET, PERIOD, &suffix, start + 2 * PERIOD as i64 - 1));
+        assert!(!verify(SECRET, PERIOD, &suffix, start + 2 * PERIOD as i64));
+        // Suffixes of a future period are not accepted yet
+        assert!(!verify(SECRET, PERIOD, &suffix, start - 1));
+    }
+
+    #[test]
+    fn verify_rejects_other_secrets() {
+        let start = 1_700_000_010;
+        assert!(!verify("another secret", PERIOD, &suffix_at(start), start));
+        assert!(!verify(SECRET, PERIOD, "", start));
+    }
+}
@@ -1,14 +1,43 @@
-use csv::Writer;
+use chrono::{DateTime, Duration, Utc};
+use csv::{Writer, WriterBuilder};
+use futures::stream::BoxStream;
+use futures::TryStreamExt;
 
 use crate::bot::database::{
-    CodeResult,
+    display_name,
+    Comment,
+    FullResponse,
+    Question,
+    RatingSummary,
+    SurveyAnswers,
     UsernameResult,
 };
+use crate::bot::error::Result;
 
 
-pub fn create_csv_body_by_code(coderes: Vec<CodeResult>) -> String {
+/// Writes the responses as they come from the database, without collecting them first
+pub async fn create_csv_body_responses(mut responses: BoxStream<'_, Result<FullResponse>>) -> Result<String> {
     let mut wtr = Writer::from_writer(vec![]);
+    while let Some(response) = responses.try_next().await? {
+        wtr.serialize(response).unwrap();
+    }
+
+    Ok(String::from_utf8(wtr.into_inner().unwrap()).unwrap())
+}
+
+pub fn create_csv_body_by_username(coderes: Vec<UsernameResult>) -> String {
+    // Same columns as a serialized response; users without check-ins get a row with their profile only
+    let mut wtr = WriterBuilder::new().has_headers(false).from_writer(vec![]);
+    wtr.write_record([
+        "id", "speech_code", "speech_title", "created_at", "telegram_id", "username", "first_name", "last_name", "rating",
+    ]).unwrap();
     for code in coderes {
+        if code.responses.is_empty() {
+            let user = code.user;
+            wtr.write_record([
+                "", "", "", "", &user.telegram_id.to_string(), &user.username, &user.first_name, &user.last_name, "",
+            ]).unwrap();
+        }
         for response in code.responses {
             wtr.serialize(response).unwrap();
         }
@@ -17,36 +46,195 @@ pub fn create_csv_body_by_code(coderes: Vec<CodeResult>) -> String {
     String::from_utf8(wtr.into_inner().unwrap()).unwrap()
 }
 
-pub fn create_csv_body_by_username(coderes: Vec<UsernameResult>) -> String {
+pub fn create_csv_body_aggregated_by_username(coderes: Vec<UsernameResult>) -> String {
+    // Format: <username or name (id)>,<code>, <code> ...,<first check-in>,<last check-in>
     let mut wtr = Writer::from_writer(vec![]);
+    wtr.write_record(["username", "speech_codes", "first_check_in", "last_check_in"]).unwrap();
     for code in coderes {
-        for response in code.responses {
-            wtr.serialize(response).unwrap();
-        }
+        let check_ins = code.responses.iter().filter_map(|r| r.created_at).collect::<Vec<DateTime<Utc>>>();
+        let speech_codes = code.responses.iter()
+            .map(|response| display_name(&response.speech_code, response.speech_title.as_deref()))
+            .collect::<Vec<String>>()
+            .join(", ");
+        wtr.write_record([
+            code.user.label(),
+            speech_codes,
+            check_ins.iter().min().map(|t| t.to_rfc3339()).unwrap_or_default(),
+            check_ins.iter().max().map(|t| t.to_rfc3339()).unwrap_or_default(),
+        ]).unwrap();
     }
 
     String::from_utf8(wtr.into_inner().unwrap()).unwrap()
 }
 
-pub fn create_csv_body_aggregated_by_username(coderes: Vec<UsernameResult>) -> String {
-    // Format: <username>,<code>,<code> ...
+pub fn create_csv_body_attendance(codes: &[String], coderes: Vec<UsernameResult>, with_times: bool) -> String {
+    // Format: <username or name (id)>,<telegram_id>,<1 or check-in time per code>...,<total>,<percentage>
     let mut wtr = Writer::from_writer(vec![]);
-    wtr.write_record(&["username", "speech_codes"]).unwrap();
+    let mut header: Vec<String> = vec!["username".to_string(), "telegram_id".to_string()];
+    header.extend(codes.iter().cloned());
+    header.extend(["total".to_string(), "percentage".to_string()]);
+    wtr.write_record(header).unwrap();
     for code in coderes {
-        let mut row: Vec<String> = Vec::new();
-        let mut speech_codes: String = String::new();
-        row.push(code.username.clone());
-        for response in code.responses {
-            if response.speech_code != "" {
-                speech_codes.push_str(&response.speech_code);
-                speech_codes.push_str(", ");
-            } else {
-                speech_codes.push_str(&format!("ID: {}", &response.id.unwrap()));
-            }
+        let mut row: Vec<String> = vec![code.user.label(), code.user.telegram_id.to_string()];
+        let mut total = 0;
+        for column in codes {
+            let response = code.responses.iter().find(|response| response.speech_code == *column);
+            total += response.is_some() as usize;
+            row.push(match response {
+                // Check-ins of unknown time still count
+                Some(response) if with_times => response.created_at.map(|t| t.to_rfc3339()).unwrap_or_else(|| "1".to_string()),
+                Some(_) => "1".to_string(),
+                None if with_times => String::new(),
+                None => "0".to_string(),
+            });
         }
-        row.push(speech_codes);
+        row.push(total.to_string());
+        row.push(if codes.is_empty() { String::new() } else { format!("{:.2}", total as f64 * 100.0 / codes.len() as f64) });
+        wtr.write_record(row).unwrap();
+    }
+
+    String::from_utf8(wtr.into_inner().unwrap()).unwrap()
+}
+
+pub fn create_csv_body_ratings(summaries: Vec<RatingSummary>) -> String {
+    // Format: <code>,<title>,<count>,<average>,<1>,<2>,<3>,<4>,<5>
+    let mut wtr = Writer::from_writer(vec![]);
+    wtr.write_record(["speech_code", "speech_title", "ratings", "average", "1", "2", "3", "4", "5"]).unwrap();
+    for summary in summaries {
+        let mut row: Vec<String> = vec![
+            summary.speech_code.clone(),
+            summary.speech_title.clone().unwrap_or_default(),
+            summary.count().to_string(),
+            summary.average().map(|a| format!("{:.2}", a)).unwrap_or_default(),
+        ];
+        row.extend(summary.distribution.iter().map(|n| n.to_string()));
+        wtr.write_record(row).unwrap();
+    }
+
+    String::from_utf8(wtr.into_inner().unwrap()).unwrap()
+}
+
+pub fn create_csv_body_comments(comments: Vec<Comment>) -> String {
+    let mut wtr = Writer::from_writer(vec![]);
+    for comment in comments {
+        wtr.serialize(comment).unwrap();
+    }
+
+    String::from_utf8(wtr.into_inner().unwrap()).unwrap()
+}
+
+pub fn create_csv_body_survey(questions: Vec<Question>, answers: Vec<SurveyAnswers>) -> String {
+    // Format: <telegram_id>,<username>,<first_name>,<last_name>,<checked_in_at>,<answer to question 1>,...
+    let mut wtr = Writer::from_writer(vec![]);
+    let mut header: Vec<String> = vec![
+        "telegram_id".to_string(),
+        "username".to_string(),
+        "first_name".to_string(),
+        "last_name".to_string(),
+        "checked_in_at".to_string(),
+    ];
+    header.extend(questions.iter().map(|q| format!("{}. {}", q.position, q.text)));
+    wtr.write_record(header).unwrap();
+    for attendee in answers {
+        let mut row: Vec<String> = vec![
+            attendee.telegram_id.to_string(),
+            attendee.username,
+            attendee.first_name,
+            attendee.last_name,
+            attendee.checked_in_at.map(|t| t.to_rfc3339()).unwrap_or_default(),
+        ];
+        row.extend(questions.iter().map(|q| attendee.answers.get(&q.id).cloned().unwrap_or_default()));
         wtr.write_record(row).unwrap();
     }
 
     String::from_utf8(wtr.into_inner().unwrap()).unwrap()
 }
+
+pub fn create_csv_body_timeline(timeline: Vec<(DateTime<Utc>, i64)>) -> String {
+    // Format: <minute>,<check-ins>,<total so far>, including minutes without check-ins
+    let mut wtr = Writer::from_writer(vec![]);
+    wtr.write_record(["minute", "check_ins", "total"]).unwrap();
+    let mut total: i64 = 0;
+    let mut bins = timeline.into_iter().peekable();
+    let mut minute = bins.peek().map(|(minute, _)| *minute);
+    while let Some(current) = minute {
+        let count = match bins.peek() {
+            Some((next, count)) if *next == current => {
+                let count = *count;
+                bins.next();
+                count
+            }
+            _ => 0,
+        };
+        total += count;
+        wtr.write_record([current.to_rfc3339(), count.to_string(), total.to_string()]).unwrap();
+        minute = bins.peek().map(|_| current + Duration::minutes(1));
+    }
+
+    String::from_utf8(wtr.into_inner().unwrap()).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::database::User;
+    use chrono::TimeZone;
+
+    fn check_in(code: &str, telegram_id: i64, created_at: Option<DateTime<Utc>>) -> FullResponse {
+        FullResponse {
+            id: None,
+            speech_code: code.to_string(),
+            speech_title: None,
+            created_at,
+            telegram_id,
+            username: String::new(),
+            first_name: String::new(),
+            last_name: String::new(),
+            rating: None,
+        }
+    }
+
+    fn attendees() -> Vec<UsernameResult> {
+        let time = Utc.with_ymd_and_hms(2024, 5, 1, 10, 30, 0).unwrap();
+        vec![
+            UsernameResult {
+                user: User { telegram_id: 1, username: "alice".to_string(), first_name: "Alice".to_string(), last_name: String::new() },
+                // Checked in to C3 before check-in times were recorded
+                responses: vec![check_in("A1", 1, Some(time)), check_in("C3", 1, None)],
+            },
+            UsernameResult {
+                user: User { telegram_id: 2, username: String::new(), first_name: "Bob".to_string(), last_name: String::new() },
+                responses: Vec::new(),
+            },
+        ]
+    }
+
+    #[test]
+    fn attendance_has_a_column_per_code() {
+        let codes = ["A1", "B2", "C3"].map(String::from);
+        assert_eq!(create_csv_body_attendance(&codes, attendees(), false), "\
+username,telegram_id,A1,B2,C3,total,percentage
+alice,1,1,0,1,2,66.67
+Bob (2),2,0,0,0,0,0.00
+");
+    }
+
+    #[test]
+    fn attendance_with_times_marks_unknown_times() {
+        let codes = ["A1", "B2", "C3"].map(String::from);
+        assert_eq!(create_csv_body_attendance(&codes, attendees(), true), "\
+username,telegram_id,A1,B2,C3,total,percentage
+alice,1,2024-05-01T10:30:00+00:00,,1,2,66.67
+Bob (2),2,,,,0,0.00
+");
+    }
+
+    #[test]
+    fn attendance_without_codes_has_no_percentage() {
+        assert_eq!(create_csv_body_attendance(&[], attendees(), false), "\
+username,telegram_id,total,percentage
+alice,1,0,
+Bob (2),2,0,
+");
+    }
+}
@@ -0,0 +1,182 @@
+use chrono::{DateTime, FixedOffset, Utc};
+use futures::stream::BoxStream;
+use futures::TryStreamExt;
+use rust_xlsxwriter::{ExcelDateTime, Format, Workbook, Worksheet};
+use std::collections::{HashMap, HashSet};
+
+use crate::bot::database::{FullResponse, Speech};
+use crate::bot::error::Result;
+
+/// Excel refuses longer sheet names
+const MAX_SHEET_NAME: usize = 31;
+
+struct Styles {
+    header: Format,
+    datetime: Format,
+}
+
+impl Styles {
+    fn new() -> Self {
+        Self {
+            header: Format::new().set_bold(),
+            datetime: Format::new().set_num_format("yyyy-mm-dd hh:mm:ss"),
+        }
+    }
+}
+
+/// A single-sheet workbook with the table of a CSV export: numbers stay numbers,
+/// RFC 3339 timestamps become dates in the given time zone
+pub fn create_xlsx_from_csv(sheet: &str, csv: &str, offset: FixedOffset) -> Result<Vec<u8>> {
+    let styles = Styles::new();
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet();
+    worksheet.set_name(sheet_name(sheet, &mut Vec::new()))?;
+
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(csv.as_bytes());
+    for (row, record) in reader.records().enumerate() {
+        let record = record?;
+        if row == 0 {
+            write_header(worksheet, &record.iter().collect::<Vec<&str>>(), &styles)?;
+            continue;
+        }
+        for (col, value) in record.iter().enumerate() {
+            write_cell(worksheet, row as u32, col as u16, value, &styles, offset)?;
+        }
+    }
+    finish_sheet(worksheet)?;
+
+    Ok(workbook.save_to_buffer()?)
+}
+
+/// A summary sheet with check-in and rating counts, then a sheet of attendees per code
+pub async fn create_xlsx_by_code(
+    speeches: Vec<Speech>,
+    mut responses: BoxStream<'_, Result<FullResponse>>,
+    offset: FixedOffset,
+) -> Result<Vec<u8>> {
+    let styles = Styles::new();
+    let mut workbook = Workbook::new();
+    let mut taken: Vec<String> = Vec::new();
+    workbook.add_worksheet().set_name(sheet_name("Summary", &mut taken))?;
+
+    // Sheet index and the next free row of each code
+    let mut sheets: HashMap<String, (usize, u32)> = HashMap::new();
+    for (index, speech) in speeches.iter().enumerate() {
+        let worksheet = workbook.add_worksheet();
+        worksheet.set_name(sheet_name(&speech.code, &mut taken))?;
+        write_header(worksheet, &["telegram_id", "username", "first_name", "last_name", "checked_in_at", "rating"], &styles)?;
+        sheets.insert(speech.code.clone(), (index + 1, 1));
+    }
+
+    let mut check_ins: HashMap<String, u32> = HashMap::new();
+    let mut ratings: HashMap<String, Vec<i32>> = HashMap::new();
+    let mut attendees: HashSet<i64> = HashSet::new();
+    while let Some(response) = responses.try_next().await? {
+        let Some((index, row)) = sheets.get_mut(&response.speech_code) else {
+            continue;
+        };
+        let worksheet = workbook.worksheet_from_index(*index)?;
+        worksheet.write_number(*row, 0, response.telegram_id as f64)?;
+        worksheet.write_string(*row, 1, &response.username)?;
+        worksheet.write_string(*row, 2, &response.first_name)?;
+        worksheet.write_string(*row, 3, &response.last_name)?;
+        if let Some(created_at) = response.created_at {
+            worksheet.write_datetime_with_format(*row, 4, excel_time(created_at, offset)?, &styles.datetime)?;
+        }
+        if let Some(rating) = response.rating {
+            worksheet.write_number(*row, 5, rating)?;
+            ratings.entry(response.speech_code.clone()).or_default().push(rating);
+        }
+        *row += 1;
+        *check_ins.entry(response.speech_code).or_default() += 1;
+        attendees.insert(response.telegram_id);
+    }
+
+    let summary = workbook.worksheet_from_index(0)?;
+    write_header(summary, &["speech_code", "speech_title", "check_ins", "ratings", "average_rating"], &styles)?;
+    let mut row: u32 = 1;
+    for speech in &speeches {
+        let speech_ratings = ratings.get(&speech.code).map(Vec::as_slice).unwrap_or_default();
+        summary.write_string(row, 0, &speech.code)?;
+        summary.write_string(row, 1, speech.title.as_deref().unwrap_or_default())?;
+        summary.write_number(row, 2, check_ins.get(&speech.code).copied().unwrap_or(0))?;
+        summary.write_number(row, 3, speech_ratings.len() as u32)?;
+        if !speech_ratings.is_empty() {
+            summary.write_number(row, 4, speech_ratings.iter().sum::<i32>() as f64 / speech_ratings.len() as f64)?;
+        }
+        row += 1;
+    }
+    summary.write_string_with_format(row + 1, 0, "total", &styles.header)?;
+    summary.write_number(row + 1, 2, check_ins.values().sum::<u32>())?;
+    summary.write_number(row + 1, 3, ratings.values().map(Vec::len).sum::<usize>() as u32)?;
+    summary.write_string_with_format(row + 2, 0, "unique_attendees", &styles.header)?;
+    summary.write_number(row + 2, 2, attendees.len() as u32)?;
+
+    for worksheet in workbook.worksheets_mut() {
+        finish_sheet(worksheet)?;
+    }
+    Ok(workbook.save_to_buffer()?)
+}
+
+fn write_header(worksheet: &mut Worksheet, header: &[&str], styles: &Styles) -> Result<()> {
+    for (col, title) in header.iter().enumerate() {
+        worksheet.write_string_with_format(0, col as u16, *title, &styles.header)?;
+    }
+    Ok(())
+}
+
+fn write_cell(worksheet: &mut Worksheet, row: u32, col: u16, value: &str, styles: &Styles, offset: FixedOffset) -> Result<()> {
+    if value.is_empty() {
+        return Ok(());
+    }
+    // Only values that print back the same, so that codes like 007 keep their zeros
+    if let Ok(number) = value.parse::<f64>() {
+        if number.is_finite() && (number.to_string() == value || format!("{:.2}", number) == value) {
+            worksheet.write_number(row, col, number)?;
+            return Ok(());
+        }
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        worksheet.write_datetime_with_format(row, col, excel_time(time.with_timezone(&Utc), offset)?, &styles.datetime)?;
+        return Ok(());
+    }
+    worksheet.write_string(row, col, value)?;
+    Ok(())
+}
+
+/// Excel has no time zones, so times are written as local ones
+fn excel_time(time: DateTime<Utc>, offset: FixedOffset) -> Result<ExcelDateTime> {
+    Ok(ExcelDateTime::from_timestamp(time.timestamp() + offset.local_minus_utc() as i64)?)
+}
+
+fn finish_sheet(worksheet: &mut Worksheet) -> Result<()> {
+    worksheet.set_freeze_panes(1, 0)?;
+    worksheet.autofit();
+    Ok(())
+}
+
+/// A valid sheet name that differs from the `taken` ones, which Excel compares case-insensitively
+fn sheet_name(name: &str, taken: &mut Vec<String>) -> String {
+    let base: String = name
+        .chars()
+        .map(|c| if "[]:*?/\\".contains(c) { '_' } else { c })
+        .collect::<String>()
+        .trim_matches('\'')
+        .chars()
+        .take(MAX_SHEET_NAME)
+        .collect();
+    let base = if base.is_empty() || base.eq_ignore_ascii_case("history") { format!("_{}", base) } else { base };
+
+    let mut candidate = base.clone();
+    let mut copy = 1;
+    while taken.iter().any(|name| name.to_lowercase() == candidate.to_lowercase()) {
+        copy += 1;
+        let suffix = format!("~{}", copy);
+        candidate = base.chars().take(MAX_SHEET_NAME - suffix.len()).collect::<String>() + &suffix;
+    }
+    taken.push(candidate.clone());
+    candidate
+}